@@ -0,0 +1 @@
+DROP TABLE replay_queue;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE replay_queue (
+    path           VARCHAR(1024),
+    user_id        INT8 NOT NULL,
+    input_channel  INT8 NOT NULL,
+    output_channel INT8 NOT NULL,
+    time_start     INT4 NOT NULL,
+    time_end       INT4 NOT NULL,
+    pitch          FLOAT8,
+    in_progress    BOOL NOT NULL DEFAULT FALSE,
+    queued_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (path)
+);
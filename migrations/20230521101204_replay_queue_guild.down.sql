@@ -0,0 +1,2 @@
+ALTER TABLE replay_queue
+DROP COLUMN guild_id;
\ No newline at end of file
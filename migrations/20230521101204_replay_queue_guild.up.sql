@@ -0,0 +1,2 @@
+ALTER TABLE replay_queue
+ADD COLUMN guild_id INT8;
@@ -0,0 +1 @@
+DROP TABLE pending_uploads;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE pending_uploads (
+    video_path     VARCHAR(1024),
+    user_id        INT8 NOT NULL,
+    output_channel INT8 NOT NULL,
+    title          VARCHAR(1024) NOT NULL,
+    mapset_id      INT4 NOT NULL,
+    replay_hash    VARCHAR(64) NOT NULL,
+    attempts       INT4 NOT NULL DEFAULT 0,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (video_path)
+);
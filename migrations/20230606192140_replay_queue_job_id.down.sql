@@ -0,0 +1,3 @@
+ALTER TABLE replay_queue
+DROP COLUMN job_id,
+DROP COLUMN name;
\ No newline at end of file
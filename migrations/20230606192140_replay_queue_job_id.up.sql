@@ -0,0 +1,3 @@
+ALTER TABLE replay_queue
+ADD COLUMN job_id VARCHAR(16),
+ADD COLUMN name   VARCHAR(256);
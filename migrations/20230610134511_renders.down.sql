@@ -0,0 +1 @@
+DROP TABLE renders;
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE renders (
+    replay_hash   VARCHAR(64),
+    settings_hash VARCHAR(32),
+    link          VARCHAR(1024) NOT NULL,
+    channel_id    INT8,
+    message_id    INT8,
+    rendered_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (replay_hash, settings_hash)
+);
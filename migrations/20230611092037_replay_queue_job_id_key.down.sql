@@ -0,0 +1,4 @@
+ALTER TABLE replay_queue
+DROP CONSTRAINT replay_queue_pkey,
+ALTER COLUMN job_id DROP NOT NULL,
+ADD PRIMARY KEY (path);
@@ -0,0 +1,8 @@
+UPDATE replay_queue
+SET job_id = LPAD(TO_HEX((RANDOM() * 4503599627370496)::INT8), 16, '0')
+WHERE job_id IS NULL;
+
+ALTER TABLE replay_queue
+DROP CONSTRAINT replay_queue_pkey,
+ALTER COLUMN job_id SET NOT NULL,
+ADD PRIMARY KEY (job_id);
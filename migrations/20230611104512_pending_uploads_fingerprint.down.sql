@@ -0,0 +1,2 @@
+ALTER TABLE pending_uploads
+DROP COLUMN settings_hash;
@@ -0,0 +1,2 @@
+ALTER TABLE pending_uploads
+ADD COLUMN settings_hash VARCHAR(32);
@@ -0,0 +1,2 @@
+ALTER TABLE renders
+DROP COLUMN guild_id;
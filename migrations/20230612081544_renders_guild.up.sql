@@ -0,0 +1,2 @@
+ALTER TABLE renders
+ADD COLUMN guild_id INT8;
@@ -0,0 +1,87 @@
+use std::{convert::TryInto, sync::Arc};
+
+use eyre::Result;
+use twilight_model::application::command::CommandOptionChoice;
+
+use crate::{
+    core::{replay_queue::Cancelled, Context},
+    util::{
+        builder::MessageBuilder, interaction::InteractionCommand, Authored, InteractionCommandExt,
+    },
+};
+
+use super::{QueueCancel, QueueCancelAutocomplete};
+
+pub async fn cancel(
+    ctx: Arc<Context>,
+    command: InteractionCommand,
+    args: QueueCancelAutocomplete,
+) -> Result<()> {
+    let user = command.user_id()?;
+
+    let QueueCancel { replay } = match args.try_into() {
+        Ok(args) => args,
+        Err(autocomplete) => {
+            let needle = autocomplete.to_ascii_lowercase();
+
+            let waiting: Vec<_> = ctx
+                .replay_queue
+                .queue
+                .lock()
+                .await
+                .iter()
+                .filter(|data| data.user == user)
+                .cloned()
+                .collect();
+
+            let active = ctx.replay_queue.active().await;
+
+            let choices = active
+                .into_iter()
+                .map(|active| active.data)
+                .filter(|data| data.user == user)
+                .chain(waiting)
+                .filter(|data| data.replay_name().to_ascii_lowercase().contains(&needle))
+                .take(25)
+                .map(|data| CommandOptionChoice::String {
+                    name: data.replay_name().to_owned(),
+                    name_localizations: None,
+                    value: data.id.to_string(),
+                })
+                .collect();
+
+            command.autocomplete(&ctx, choices).await?;
+
+            return Ok(());
+        }
+    };
+
+    let content = match ctx.replay_queue.cancel(ctx.psql(), user, &replay).await {
+        Cancelled::Waiting(data) => {
+            format!("Removed `{}` from the queue", data.replay_name())
+        }
+        Cancelled::Active(data) => {
+            format!("Stopped processing `{}`", data.replay_name())
+        }
+        Cancelled::Uploading(data) => {
+            let content = format!(
+                "`{}` is already being uploaded and can't be cancelled anymore",
+                data.replay_name()
+            );
+            command.error_callback(&ctx, content, true).await?;
+
+            return Ok(());
+        }
+        Cancelled::NotFound => {
+            let content = "None of your replays in the queue matches that name";
+            command.error_callback(&ctx, content, true).await?;
+
+            return Ok(());
+        }
+    };
+
+    let builder = MessageBuilder::new().embed(content);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
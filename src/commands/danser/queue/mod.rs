@@ -0,0 +1,86 @@
+use std::{convert::TryFrom, sync::Arc};
+
+use command_macros::SlashCommand;
+use eyre::Result;
+use twilight_interactions::command::{AutocompleteValue, CommandModel, CreateCommand};
+
+use crate::{
+    core::Context,
+    util::{interaction::InteractionCommand, InteractionCommandExt},
+};
+
+use self::{cancel::*, reupload::*, view::*};
+
+mod cancel;
+mod reupload;
+mod view;
+
+#[derive(CreateCommand, SlashCommand)]
+#[command(name = "queue")]
+#[flags(SKIP_DEFER)]
+#[allow(unused)]
+/// Displays or modifies the replay queue
+pub enum Queue {
+    #[command(name = "view")]
+    View(QueueView),
+    #[command(name = "cancel")]
+    Cancel(QueueCancel),
+    #[command(name = "reupload")]
+    Reupload(QueueReupload),
+}
+
+#[derive(CommandModel)]
+pub enum QueueParsable {
+    #[command(name = "view")]
+    View(QueueView),
+    #[command(name = "cancel")]
+    Cancel(QueueCancelAutocomplete),
+    #[command(name = "reupload")]
+    Reupload(QueueReupload),
+}
+
+#[derive(CreateCommand, CommandModel)]
+#[command(name = "view")]
+/// Displays the current replay queue
+pub struct QueueView;
+
+#[derive(CreateCommand)]
+#[command(name = "cancel")]
+/// Cancel one of your replays in the queue
+pub struct QueueCancel {
+    #[command(autocomplete = true)]
+    /// The replay you want to cancel
+    replay: String,
+}
+
+#[derive(CreateCommand, CommandModel)]
+#[command(name = "reupload")]
+/// Retry uploading your rendered replays whose upload failed
+pub struct QueueReupload;
+
+impl TryFrom<QueueCancelAutocomplete> for QueueCancel {
+    type Error = String;
+
+    #[inline]
+    fn try_from(cancel: QueueCancelAutocomplete) -> Result<Self, Self::Error> {
+        match cancel.replay {
+            AutocompleteValue::Focused(replay) => Err(replay),
+            AutocompleteValue::None => Err(String::new()),
+            AutocompleteValue::Completed(replay) => Ok(Self { replay }),
+        }
+    }
+}
+
+#[derive(CommandModel)]
+#[command(autocomplete = true)]
+pub struct QueueCancelAutocomplete {
+    replay: AutocompleteValue<String>,
+}
+
+pub async fn slash_queue(ctx: Arc<Context>, mut command: InteractionCommand) -> Result<()> {
+    match QueueParsable::from_interaction(command.input_data())? {
+        QueueParsable::View(_) => view(ctx, command).await,
+        QueueParsable::Cancel(args) => cancel(ctx, command, args).await,
+        QueueParsable::Reupload(_) => reupload(ctx, command).await,
+    }
+}
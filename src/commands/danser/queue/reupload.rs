@@ -0,0 +1,33 @@
+use std::{fmt::Write, sync::Arc};
+
+use eyre::Result;
+
+use crate::{
+    core::Context,
+    util::{
+        builder::MessageBuilder, interaction::InteractionCommand, Authored, InteractionCommandExt,
+    },
+};
+
+pub async fn reupload(ctx: Arc<Context>, command: InteractionCommand) -> Result<()> {
+    let user = command.user_id()?;
+    let titles = ctx.replay_queue.pending_uploads.retry_now(user).await;
+
+    if titles.is_empty() {
+        let content = "None of your rendered replays is waiting to be uploaded";
+        command.error_callback(&ctx, content, true).await?;
+
+        return Ok(());
+    }
+
+    let mut content = String::from("Retrying the upload of:");
+
+    for title in titles {
+        let _ = write!(content, "\n- {title}");
+    }
+
+    let builder = MessageBuilder::new().embed(content);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
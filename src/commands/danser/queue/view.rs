@@ -0,0 +1,153 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use eyre::Result;
+use time::OffsetDateTime;
+use twilight_model::channel::embed::EmbedField;
+
+use crate::{
+    core::{ActiveReplay, BotConfig, Context, ReplayData, ReplayStatus},
+    util::{
+        builder::{EmbedBuilder, FooterBuilder, MessageBuilder},
+        datetime::from_now_dynamic,
+        interaction::InteractionCommand,
+        InteractionCommandExt,
+    },
+};
+
+/// Maximum length of an embed field's value
+const FIELD_VALUE_LIMIT: usize = 1024;
+
+pub async fn view(ctx: Arc<Context>, command: InteractionCommand) -> Result<()> {
+    let queue_guard = ctx.replay_queue.queue.lock().await;
+    let active = ctx.replay_queue.active().await;
+
+    let mut embed = EmbedBuilder::new()
+        .title("Current queue")
+        .timestamp(OffsetDateTime::now_utc());
+
+    if active.is_empty() && queue_guard.is_empty() {
+        embed = embed.description("The queue is empty");
+    } else {
+        let estimates = ctx.replay_queue.estimate_with(&active, &queue_guard);
+
+        let mut fields: Vec<_> = active
+            .iter()
+            .zip(estimates.active)
+            .map(|(ActiveReplay { data, status, .. }, eta)| EmbedField {
+                inline: false,
+                name: "Progress".to_owned(),
+                value: progress_value(data, *status, eta),
+            })
+            .collect();
+
+        if !queue_guard.is_empty() {
+            let mut value = String::with_capacity(128);
+            let mut shown = 0;
+
+            let iter = queue_guard
+                .iter()
+                .zip(estimates.waiting)
+                .zip(active.len() + 1..);
+
+            for ((data, eta), idx) in iter {
+                let line = format!(
+                    "`{idx}.` <@{}>: {} (done {})\n",
+                    data.user,
+                    data.replay_name(),
+                    from_now_dynamic(eta),
+                );
+
+                // Leave room for the line mentioning the remaining replays
+                if value.len() + line.len() > FIELD_VALUE_LIMIT - 32 {
+                    break;
+                }
+
+                value.push_str(&line);
+                shown += 1;
+            }
+
+            let remaining = queue_guard.len() - shown;
+
+            if remaining > 0 {
+                let _ = write!(value, "…and {remaining} more");
+            }
+
+            fields.push(EmbedField {
+                inline: false,
+                name: "Upcoming".to_owned(),
+                value,
+            });
+        }
+
+        embed = embed.fields(fields);
+    }
+
+    if ctx.replay_queue.is_paused() {
+        embed = embed.footer(FooterBuilder::new(
+            "The queue is paused, upcoming replays won't start for now",
+        ));
+    }
+
+    let builder = MessageBuilder::new().embed(embed);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
+
+fn progress_value(data: &ReplayData, status: ReplayStatus, eta: Duration) -> String {
+    format!(
+        "<@{user}>: {name}\n\
+        • Downloading: {downloading}\n\
+        • Rendering: {rendering}\n\
+        • Encoding: {encoding}\n\
+        • Uploading: {uploading}\n\
+        • Done: {eta}",
+        user = data.user,
+        name = data.replay_name(),
+        downloading = match status {
+            ReplayStatus::Waiting => ProcessStatus::Waiting,
+            ReplayStatus::Downloading => ProcessStatus::Running(None),
+            _ => ProcessStatus::Done,
+        },
+        rendering = match status {
+            ReplayStatus::Waiting | ReplayStatus::Downloading => ProcessStatus::Waiting,
+            ReplayStatus::Rendering(progress) => ProcessStatus::Running(Some(progress)),
+            _ => ProcessStatus::Done,
+        },
+        encoding = match status {
+            ReplayStatus::Encoding(progress) => ProcessStatus::Running(Some(progress)),
+            ReplayStatus::Uploading(_) => ProcessStatus::Done,
+            _ => ProcessStatus::Waiting,
+        },
+        uploading = if let ReplayStatus::Uploading(progress) = status {
+            ProcessStatus::Running(Some(progress))
+        } else {
+            ProcessStatus::Waiting
+        },
+        eta = from_now_dynamic(eta),
+    )
+}
+
+enum ProcessStatus {
+    Done,
+    Running(Option<u8>),
+    Waiting,
+}
+
+impl Display for ProcessStatus {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ProcessStatus::Done => write!(f, "{}", BotConfig::get().emojis.white_check_mark),
+            ProcessStatus::Running(Some(progress)) => {
+                write!(f, "{} ({progress}%)", BotConfig::get().emojis.man_running)
+            }
+            ProcessStatus::Running(None) => write!(f, "{}", BotConfig::get().emojis.man_running),
+            ProcessStatus::Waiting => write!(f, "{}", BotConfig::get().emojis.hourglass),
+        }
+    }
+}
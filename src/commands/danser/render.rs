@@ -1,16 +1,30 @@
 use command_macros::SlashCommand;
-use eyre::{Context as _, Result};
+use eyre::{Context as _, Report, Result};
 use osu_db::{Mode, Replay};
 use std::fmt::Write;
-use std::sync::Arc;
-use tokio::{fs::File, io::AsyncWriteExt};
+use std::{sync::Arc, time::Duration};
+use tokio::{fs::File, io::AsyncWriteExt, time};
 use twilight_interactions::command::{CommandModel, CreateCommand};
-use twilight_model::channel::Attachment;
+use twilight_model::{
+    application::{
+        component::{button::ButtonStyle, ActionRow, Button, Component},
+        interaction::Interaction,
+    },
+    channel::Attachment,
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{marker::UserMarker, Id},
+};
 
 use crate::{
-    core::{BotConfig, Context, ReplayData, TimePoints},
+    core::{
+        replay_queue::{
+            existing_render, render_fingerprint, replay_name_from_file, JobId, ProgressMessage,
+        },
+        BotConfig, Context, ReplayData, TimePoints,
+    },
     util::{
-        builder::MessageBuilder, interaction::InteractionCommand, Authored, InteractionCommandExt,
+        builder::MessageBuilder, constants::DISK_FULL, datetime::from_now_dynamic,
+        interaction::InteractionCommand, Authored, InteractionCommandExt,
     },
 };
 
@@ -48,6 +62,25 @@ pub async fn slash_render(ctx: Arc<Context>, mut command: InteractionCommand) ->
         return Ok(());
     }
 
+    if ctx.janitor.is_disk_critical() {
+        command.error_callback(&ctx, DISK_FULL, true).await?;
+
+        return Ok(());
+    }
+
+    let user = command.user_id()?;
+    let max_per_user = BotConfig::get().queue.max_per_user;
+
+    if ctx.replay_queue.queued_by(user).await >= max_per_user {
+        let content = format!(
+            "You already have {max_per_user} replays in the queue, \
+            wait for them to finish first!"
+        );
+        command.error_callback(&ctx, content, true).await?;
+
+        return Ok(());
+    }
+
     let start_in_seconds = if let Some(start) = start {
         match TimePoints::parse_single(&start) {
             Ok(start) => start,
@@ -148,9 +181,40 @@ pub async fn slash_render(ctx: Arc<Context>, mut command: InteractionCommand) ->
         return Ok(());
     }
 
-    let config = BotConfig::get();
-    let mut replay_file = config.paths.downloads();
-    replay_file.push(attachment.filename);
+    let time_points = TimePoints {
+        start: start_in_seconds,
+        end: end_in_seconds,
+    };
+
+    if let Some(replay_hash) = replay.replay_hash.as_deref() {
+        let fingerprint = render_fingerprint(user, time_points, pitch).await;
+
+        if let Some(link) = existing_render(&ctx, replay_hash, &fingerprint, command.guild_id).await
+        {
+            if !render_again(&ctx, &command, user, &link).await? {
+                return Ok(());
+            }
+        }
+    }
+
+    // The length is only needed for time estimates so it's fine if this fails
+    let map_length = match replay.beatmap_hash.as_deref() {
+        Some(hash) => match ctx.osu().beatmap().checksum(hash).await {
+            Ok(map) => Some(map.seconds_total),
+            Err(err) => {
+                warn!(
+                    "{:?}",
+                    Report::new(err).wrap_err("failed to get map for replay")
+                );
+
+                None
+            }
+        },
+        None => None,
+    };
+
+    let id = JobId::generate();
+    let replay_file = id.replay_path();
 
     let mut file = match File::create(&replay_file).await {
         Ok(file) => file,
@@ -168,24 +232,105 @@ pub async fn slash_render(ctx: Arc<Context>, mut command: InteractionCommand) ->
     };
 
     let replay_data = ReplayData {
+        id,
+        name: replay_name_from_file(&attachment.filename),
+        guild: command.guild_id,
         input_channel: command.channel_id,
+        map_length,
         output_channel,
         pitch,
         path: replay_file,
+        progress: Some(ProgressMessage::interaction(&command.token)),
         replay: replay.into(),
-        time_points: TimePoints {
-            start: start_in_seconds,
-            end: end_in_seconds,
-        },
-        user: command.user_id()?,
+        time_points,
+        user,
     };
 
-    ctx.replay_queue.push(replay_data).await;
+    let eta = ctx.replay_queue.push(ctx.psql(), replay_data).await;
+
+    let content = match eta {
+        Some(eta) => format!(
+            "Replay has been added to the queue!\n\
+            It should be done {}",
+            from_now_dynamic(eta)
+        ),
+        None => "Replay has been added to the queue!".to_owned(),
+    };
 
-    let content = "Replay has been added to the queue!";
-    let builder = MessageBuilder::new().embed(content);
+    let builder = MessageBuilder::new().embed(content).components(Vec::new());
 
     command.update(&ctx, &builder).await?;
 
     Ok(())
 }
+
+/// How long the user has to decide whether an identical replay should be rendered again
+const RENDER_AGAIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Point the user to the existing render of the replay and
+/// wait for them to decide whether it should be rendered anyway.
+pub(super) async fn render_again(
+    ctx: &Context,
+    command: &InteractionCommand,
+    user: Id<UserMarker>,
+    link: &str,
+) -> Result<bool> {
+    let content = format!(
+        "This replay has already been rendered with the same settings: {link}\n\
+        Press the button within a minute if you want it rendered again anyway."
+    );
+
+    let button = Button {
+        custom_id: Some("render_force".to_owned()),
+        disabled: false,
+        emoji: None,
+        label: Some("Render again".to_owned()),
+        style: ButtonStyle::Secondary,
+        url: None,
+    };
+
+    let components = vec![Component::ActionRow(ActionRow {
+        components: vec![Component::Button(button)],
+    })];
+
+    let builder = MessageBuilder::new().embed(content).components(components);
+
+    let msg = command
+        .update(ctx, &builder)
+        .await?
+        .model()
+        .await
+        .context("failed to deserialize response message")?;
+
+    let fut = ctx
+        .standby
+        .wait_for_component(msg.id, move |event: &Interaction| {
+            event.author_id() == Some(user)
+        });
+
+    let component = match time::timeout(RENDER_AGAIN_TIMEOUT, fut).await {
+        Ok(Ok(component)) => component,
+        // Timed out or the standby was dropped, keep the link and remove the button
+        Ok(Err(_)) | Err(_) => {
+            let content =
+                format!("This replay has already been rendered with the same settings: {link}");
+            let builder = MessageBuilder::new().embed(content).components(Vec::new());
+            command.update(ctx, &builder).await?;
+
+            return Ok(false);
+        }
+    };
+
+    let response = InteractionResponse {
+        kind: InteractionResponseType::DeferredUpdateMessage,
+        data: None,
+    };
+
+    ctx.interaction()
+        .create_response(component.id, &component.token, &response)
+        .exec()
+        .await
+        .context("failed to acknowledge render button")?;
+
+    Ok(true)
+}
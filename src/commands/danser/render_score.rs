@@ -9,14 +9,42 @@ use twilight_interactions::command::CommandInputData;
 use twilight_model::{channel::embed::Embed, util::Timestamp};
 
 use crate::{
-    core::{replay_queue::ReplaySlim, BotConfig, Context, ReplayData, TimePoints},
+    core::{
+        replay_queue::{
+            existing_render, render_fingerprint, sanitize_replay_name, JobId, ProgressMessage,
+            ReplaySlim,
+        },
+        BotConfig, Context, ReplayData, TimePoints,
+    },
     util::{
-        builder::MessageBuilder, interaction::InteractionCommand, Authored, InteractionCommandExt,
+        builder::MessageBuilder, constants::DISK_FULL, datetime::from_now_dynamic,
+        interaction::InteractionCommand, Authored, InteractionCommandExt,
     },
 };
 
+use super::render::render_again;
+
 #[msg_command(name = "Render score", dm_permission = false)]
 async fn render_from_msg(ctx: Arc<Context>, mut command: InteractionCommand) -> Result<()> {
+    if ctx.janitor.is_disk_critical() {
+        command.error(&ctx, DISK_FULL).await?;
+
+        return Ok(());
+    }
+
+    let user = command.user_id()?;
+    let max_per_user = BotConfig::get().queue.max_per_user;
+
+    if ctx.replay_queue.queued_by(user).await >= max_per_user {
+        let content = format!(
+            "You already have {max_per_user} replays in the queue, \
+            wait for them to finish first!"
+        );
+        command.error(&ctx, content).await?;
+
+        return Ok(());
+    }
+
     let input_data = command.input_data();
 
     let (osu_user_id, timestamp) = match parse_embed(&input_data) {
@@ -101,6 +129,7 @@ async fn render_from_msg(ctx: Arc<Context>, mut command: InteractionCommand) ->
         None => "<unknown user>".into(),
     };
 
+    let map_length = score_to_render.map.as_ref().map(|map| map.seconds_total);
     let replay_mapset = score_to_render.mapset;
 
     let map_title = match replay_mapset {
@@ -108,8 +137,8 @@ async fn render_from_msg(ctx: Arc<Context>, mut command: InteractionCommand) ->
         None => "<unknown map>".into(),
     };
 
-    let mut path = BotConfig::get().paths.downloads();
-    path.push(format!("{osu_user} - {map_title}.osr"));
+    let id = JobId::generate();
+    let path = id.replay_path();
 
     fs::write(&path, &replay_bytes).context("failed to write into replay file")?;
 
@@ -123,8 +152,21 @@ async fn render_from_msg(ctx: Arc<Context>, mut command: InteractionCommand) ->
         }
     };
 
+    if let Some(replay_hash) = replay.replay_hash.as_deref() {
+        let time_points = TimePoints { start: 0, end: 0 };
+        let fingerprint = render_fingerprint(user, time_points, None).await;
+
+        if let Some(link) = existing_render(&ctx, replay_hash, &fingerprint, command.guild_id).await
+        {
+            if !render_again(&ctx, &command, user, &link).await? {
+                let _ = fs::remove_file(&path);
+
+                return Ok(());
+            }
+        }
+    }
+
     let input_channel = command.channel_id;
-    let user = command.user_id()?;
 
     let guild_id = command.guild_id().context("expected guild id")?;
     let output_channel = ctx
@@ -133,18 +175,32 @@ async fn render_from_msg(ctx: Arc<Context>, mut command: InteractionCommand) ->
         .unwrap_or(input_channel);
 
     let replay_data = ReplayData {
+        id,
+        name: sanitize_replay_name(&format!("{osu_user} - {map_title}")),
+        guild: Some(guild_id),
         input_channel,
+        map_length,
         output_channel,
         pitch: None,
         path,
+        progress: Some(ProgressMessage::interaction(&command.token)),
         replay,
         user,
         time_points: TimePoints { start: 0, end: 0 },
     };
 
-    ctx.replay_queue.push(replay_data).await;
+    let eta = ctx.replay_queue.push(ctx.psql(), replay_data).await;
 
-    let builder = MessageBuilder::new().embed("Replay has been pushed to the queue!");
+    let content = match eta {
+        Some(eta) => format!(
+            "Replay has been pushed to the queue!\n\
+            It should be done {}",
+            from_now_dynamic(eta)
+        ),
+        None => "Replay has been pushed to the queue!".to_owned(),
+    };
+
+    let builder = MessageBuilder::new().embed(content).components(Vec::new());
     command.update(&ctx, &builder).await?;
 
     Ok(())
@@ -214,7 +270,13 @@ fn extend_replay_bytes(bytes: &mut Vec<u8>, score: &Score) {
 
     bytes_written += encode_string(bytes, username);
 
-    let replay_md5 = String::new();
+    // The API doesn't provide the replay's hash but the score id identifies the replay just as
+    // well. Renders and uploads are keyed by this hash so they can't do without it. Without
+    // score id the hash is random so the replay is never mistaken for another one.
+    let replay_md5 = match score.score_id {
+        Some(score_id) => format!("{:x}", md5::compute(score_id.to_le_bytes())),
+        None => format!("{:032x}", rand::random::<u128>()),
+    };
     bytes_written += encode_string(bytes, &replay_md5);
 
     let stats = &score.statistics;
@@ -0,0 +1,63 @@
+use std::{io::ErrorKind, sync::Arc};
+
+use eyre::{Context as _, Result};
+use tokio::fs;
+
+use crate::{
+    core::replay_queue::JobId,
+    util::{builder::MessageBuilder, interaction::InteractionCommand, InteractionCommandExt},
+    Context,
+};
+
+use super::OwnerLog;
+
+/// Only the end of larger logs is sent so it fits into an attachment
+const MAX_LOG_SIZE: usize = 8 * 1024 * 1024;
+
+pub async fn log(ctx: Arc<Context>, command: InteractionCommand, args: OwnerLog) -> Result<()> {
+    let id: JobId = match args.job.trim().trim_matches('`').parse() {
+        Ok(id) => id,
+        Err(_) => {
+            let content = "That's not a valid job id";
+            command.error_callback(&ctx, content, true).await?;
+
+            return Ok(());
+        }
+    };
+
+    let path = id.log_path();
+
+    let mut bytes = match fs::read(&path).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let content = format!(
+                "There is no log for job `{id}`, \
+                either the render did not fail or the log was cleaned up already"
+            );
+            command.error_callback(&ctx, content, true).await?;
+
+            return Ok(());
+        }
+        Err(err) => {
+            let _ = command
+                .error_callback(&ctx, "Failed to read the log", true)
+                .await;
+
+            return Err(err).with_context(|| format!("failed to read log at {path:?}"));
+        }
+    };
+
+    if bytes.len() > MAX_LOG_SIZE {
+        bytes.drain(..bytes.len() - MAX_LOG_SIZE);
+    }
+
+    command.defer(&ctx, true).await?;
+
+    let builder = MessageBuilder::new()
+        .content(format!("danser log of job `{id}`"))
+        .attachment(format!("{id}.log"), bytes);
+
+    command.update(&ctx, &builder).await?;
+
+    Ok(())
+}
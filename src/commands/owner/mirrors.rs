@@ -0,0 +1,54 @@
+use std::{fmt::Write, sync::Arc, time::Instant};
+
+use eyre::Result;
+
+use crate::{
+    util::{
+        builder::{EmbedBuilder, MessageBuilder},
+        datetime::from_now_dynamic,
+        interaction::InteractionCommand,
+        InteractionCommandExt,
+    },
+    Context,
+};
+
+pub async fn mirrors(ctx: Arc<Context>, command: InteractionCommand) -> Result<()> {
+    let mut description = String::new();
+
+    for (i, mirror) in ctx.client().mirrors().iter().enumerate() {
+        let (rate, total) = mirror.success_rate();
+
+        let _ = write!(
+            description,
+            "{idx}. `{name}`: {rate:.0}% of the last {total} download(s) succeeded",
+            idx = i + 1,
+            name = mirror.name(),
+            rate = rate * 100.0,
+        );
+
+        if let Some(until) = mirror.skipped_until() {
+            let remaining = until.saturating_duration_since(Instant::now());
+            let _ = write!(
+                description,
+                "\n  ↳ skipped until {}",
+                from_now_dynamic(remaining)
+            );
+        }
+
+        description.push('\n');
+    }
+
+    if description.is_empty() {
+        description.push_str("No mirrors configured");
+    }
+
+    let embed = EmbedBuilder::new()
+        .title("Mapset mirrors")
+        .description(description)
+        .build();
+
+    let builder = MessageBuilder::new().embed(embed);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
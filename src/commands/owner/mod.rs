@@ -9,9 +9,14 @@ use crate::{
     Context,
 };
 
-use self::cache::*;
+use self::{cache::*, log::*, mirrors::*, queue::*, ratelimits::*, renders::*};
 
 mod cache;
+mod log;
+mod mirrors;
+mod queue;
+mod ratelimits;
+mod renders;
 
 #[derive(CommandModel, CreateCommand, SlashCommand)]
 #[command(name = "owner")]
@@ -20,6 +25,16 @@ mod cache;
 pub enum Owner {
     #[command(name = "cache")]
     Cache(OwnerCache),
+    #[command(name = "log")]
+    Log(OwnerLog),
+    #[command(name = "mirrors")]
+    Mirrors(OwnerMirrors),
+    #[command(name = "queue")]
+    Queue(OwnerQueue),
+    #[command(name = "ratelimits")]
+    Ratelimits(OwnerRatelimits),
+    #[command(name = "renders")]
+    Renders(OwnerRenders),
 }
 
 #[derive(CommandModel, CreateCommand)]
@@ -27,6 +42,86 @@ pub enum Owner {
 /// Display stats about the internal cache
 pub struct OwnerCache;
 
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "log")]
+/// Fetch the danser log of a failed render
+pub struct OwnerLog {
+    /// Job id as shown in the failure message
+    job: String,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "mirrors")]
+/// Display the health of the mapset mirrors
+pub struct OwnerMirrors;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "ratelimits")]
+/// Display the rate limits and timeouts of external sites
+pub struct OwnerRatelimits;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "renders")]
+/// Display stats about renders since the last restart
+pub struct OwnerRenders;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "queue")]
+/// Manage the replay queue
+pub enum OwnerQueue {
+    #[command(name = "pause")]
+    Pause(OwnerQueuePause),
+    #[command(name = "resume")]
+    Resume(OwnerQueueResume),
+    #[command(name = "remove")]
+    Remove(OwnerQueueRemove),
+    #[command(name = "move")]
+    Move(OwnerQueueMove),
+    #[command(name = "clear")]
+    Clear(OwnerQueueClear),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "pause")]
+/// Stop starting new renders, renders in progress will still finish
+pub struct OwnerQueuePause;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "resume")]
+/// Continue rendering after the queue was paused
+pub struct OwnerQueueResume;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "remove")]
+/// Remove a replay from the queue or stop it if it's in progress
+pub struct OwnerQueueRemove {
+    #[command(min_value = 1, max_value = 65_535)]
+    /// Position of the replay as shown in /queue view
+    position: usize,
+    /// Reason that will be shown to the user
+    reason: Option<String>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "move")]
+/// Move an upcoming replay to a different position
+pub struct OwnerQueueMove {
+    #[command(min_value = 1, max_value = 65_535)]
+    /// Current position of the replay as shown in /queue view
+    from: usize,
+    #[command(min_value = 1, max_value = 65_535)]
+    /// New position of the replay
+    to: usize,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "clear")]
+/// Remove all upcoming replays from the queue
+pub struct OwnerQueueClear {
+    /// Reason that will be shown to the users
+    reason: Option<String>,
+}
+
 // * EXAMPLE:
 // #[derive(CommandModel, CreateCommand)]
 // #[command(name = "interval")]
@@ -39,5 +134,10 @@ pub struct OwnerCache;
 async fn slash_owner(ctx: Arc<Context>, mut command: InteractionCommand) -> Result<()> {
     match Owner::from_interaction(command.input_data())? {
         Owner::Cache(_) => cache(ctx, command).await,
+        Owner::Log(args) => log(ctx, command, args).await,
+        Owner::Mirrors(_) => mirrors(ctx, command).await,
+        Owner::Queue(args) => queue(ctx, command, args).await,
+        Owner::Ratelimits(_) => ratelimits(ctx, command).await,
+        Owner::Renders(_) => renders(ctx, command).await,
     }
 }
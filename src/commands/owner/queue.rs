@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt::Write, slice, sync::Arc};
+
+use eyre::Result;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::{
+    core::{replay_queue::Cancelled, ReplayData},
+    util::{
+        builder::MessageBuilder, interaction::InteractionCommand, ChannelExt, InteractionCommandExt,
+    },
+    Context,
+};
+
+use super::{OwnerQueue, OwnerQueueClear, OwnerQueueMove, OwnerQueueRemove};
+
+pub async fn queue(ctx: Arc<Context>, command: InteractionCommand, args: OwnerQueue) -> Result<()> {
+    let content = match args {
+        OwnerQueue::Pause(_) => {
+            if ctx.replay_queue.pause() {
+                "Paused the queue, replays that are in progress will still finish".to_owned()
+            } else {
+                "The queue is already paused".to_owned()
+            }
+        }
+        OwnerQueue::Resume(_) => {
+            if ctx.replay_queue.resume() {
+                "Resumed the queue".to_owned()
+            } else {
+                "The queue is not paused".to_owned()
+            }
+        }
+        OwnerQueue::Remove(OwnerQueueRemove { position, reason }) => {
+            match ctx.replay_queue.remove_at(ctx.psql(), position).await {
+                Cancelled::Waiting(data) | Cancelled::Active(data) => {
+                    notify_removed(&ctx, slice::from_ref(&data), reason.as_deref()).await;
+
+                    format!(
+                        "Removed `{name}` of <@{user}> from the queue",
+                        name = data.replay_name(),
+                        user = data.user,
+                    )
+                }
+                Cancelled::Uploading(data) => {
+                    let content = format!(
+                        "`{}` is already being uploaded and can't be removed anymore",
+                        data.replay_name()
+                    );
+                    command.error_callback(&ctx, content, false).await?;
+
+                    return Ok(());
+                }
+                Cancelled::NotFound => {
+                    let content = format!("There is no replay at position {position}");
+                    command.error_callback(&ctx, content, false).await?;
+
+                    return Ok(());
+                }
+            }
+        }
+        OwnerQueue::Move(OwnerQueueMove { from, to }) => {
+            match ctx.replay_queue.move_entry(from, to).await {
+                Some(data) => format!(
+                    "Moved `{name}` from position {from} to {to}",
+                    name = data.replay_name(),
+                ),
+                None => {
+                    let content = "Both positions must be within the upcoming replays, \
+                        replays that are already in progress can't be moved";
+                    command.error_callback(&ctx, content, false).await?;
+
+                    return Ok(());
+                }
+            }
+        }
+        OwnerQueue::Clear(OwnerQueueClear { reason }) => {
+            let removed = ctx.replay_queue.clear(ctx.psql()).await;
+            notify_removed(&ctx, &removed, reason.as_deref()).await;
+
+            format!("Removed {} replays from the queue", removed.len())
+        }
+    };
+
+    let builder = MessageBuilder::new().embed(content);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
+
+/// Discord's limit for the characters in a message's content
+const CONTENT_LIMIT: usize = 2000;
+
+/// Let users know in their input channels that their replays were removed
+async fn notify_removed(ctx: &Context, removed: &[ReplayData], reason: Option<&str>) {
+    let mut channels: HashMap<Id<ChannelMarker>, Vec<&ReplayData>> = HashMap::new();
+
+    for data in removed {
+        channels.entry(data.input_channel).or_default().push(data);
+    }
+
+    let reason = reason
+        .map(|reason| {
+            let reason: String = reason.chars().take(200).collect();
+
+            format!("\nReason: {reason}")
+        })
+        .unwrap_or_default();
+
+    for (channel, replays) in channels {
+        let mut content =
+            String::from("The following replays were removed from the queue by the bot owner:");
+
+        let tail = format!("\n• ...and {} more", replays.len());
+
+        // Byte lengths are at least the character counts so the content stays within the limit
+        let limit = CONTENT_LIMIT - reason.len() - tail.len();
+
+        for (data, i) in replays.iter().zip(0..) {
+            let entry = format!("\n• <@{}>: {}", data.user, data.replay_name());
+
+            if content.len() + entry.len() > limit {
+                let _ = write!(content, "\n• ...and {} more", replays.len() - i);
+
+                break;
+            }
+
+            content.push_str(&entry);
+        }
+
+        content.push_str(&reason);
+
+        let builder = MessageBuilder::new().content(content);
+
+        if let Err(err) = channel.create_message(ctx, &builder).await {
+            warn!("failed to notify users about removed replays: {err}");
+        }
+    }
+}
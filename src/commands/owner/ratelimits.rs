@@ -0,0 +1,37 @@
+use std::{fmt::Write, sync::Arc};
+
+use eyre::Result;
+
+use crate::{
+    util::{
+        builder::{EmbedBuilder, MessageBuilder},
+        interaction::InteractionCommand,
+        InteractionCommandExt,
+    },
+    Context,
+};
+
+pub async fn ratelimits(ctx: Arc<Context>, command: InteractionCommand) -> Result<()> {
+    let mut description = String::new();
+
+    for state in ctx.client().ratelimits() {
+        let _ = writeln!(
+            description,
+            "`{name}`: {tokens}/{max} requests available, {timeout}s timeout",
+            name = state.name,
+            tokens = state.tokens,
+            max = state.max,
+            timeout = state.timeout.as_secs(),
+        );
+    }
+
+    let embed = EmbedBuilder::new()
+        .title("Rate limits of the custom client")
+        .description(description)
+        .build();
+
+    let builder = MessageBuilder::new().embed(embed);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
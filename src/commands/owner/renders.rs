@@ -0,0 +1,42 @@
+use std::{fmt::Write, sync::Arc};
+
+use eyre::Result;
+
+use crate::{
+    util::{
+        builder::{EmbedBuilder, MessageBuilder},
+        interaction::InteractionCommand,
+        numbers::with_comma_int,
+        InteractionCommandExt,
+    },
+    Context,
+};
+
+pub async fn renders(ctx: Arc<Context>, command: InteractionCommand) -> Result<()> {
+    let stats = &ctx.stats.renders;
+    let failures = stats.failures();
+    let failed: u64 = failures.iter().map(|(_, count)| count).sum();
+
+    let mut description = format!(
+        "Succeeded: {succeeded}\n\
+        Cancelled: {cancelled}\n\
+        Failed: {failed}",
+        succeeded = with_comma_int(stats.succeeded()),
+        cancelled = with_comma_int(stats.cancelled()),
+        failed = with_comma_int(failed),
+    );
+
+    for (kind, count) in failures {
+        let _ = write!(description, "\n• {kind}: {}", with_comma_int(count));
+    }
+
+    let embed = EmbedBuilder::new()
+        .title("Renders since the last restart")
+        .description(description)
+        .build();
+
+    let builder = MessageBuilder::new().embed(embed);
+    command.callback(&ctx, builder, false).await?;
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, time::Duration};
 
 use eyre::{Context, ContextCompat, Result};
 use once_cell::sync::OnceCell;
@@ -17,7 +17,11 @@ pub struct BotConfig {
     pub emojis: Emojis,
     pub owners: Vec<Id<UserMarker>>,
     pub dev_guild: Id<GuildMarker>,
-    pub upload_url: String,
+    pub upload: UploadConfig,
+    pub queue: QueueConfig,
+    pub mapsets: MapsetConfig,
+    pub client: ClientConfig,
+    pub janitor: JanitorConfig,
 }
 
 #[derive(Debug)]
@@ -38,6 +42,10 @@ impl Paths {
         &self.danser
     }
 
+    pub fn folders(&self) -> &PathBuf {
+        &self.folders
+    }
+
     pub fn downloads(&self) -> PathBuf {
         let mut path = self.folders.clone();
         path.push("Downloads");
@@ -45,6 +53,13 @@ impl Paths {
         path
     }
 
+    pub fn logs(&self) -> PathBuf {
+        let mut path = self.folders.clone();
+        path.push("Logs");
+
+        path
+    }
+
     pub fn replays(&self) -> PathBuf {
         let mut path = self.folders.clone();
         path.push("Replays");
@@ -60,6 +75,14 @@ impl Paths {
         path
     }
 
+    /// Contains a working directory for each running render
+    pub fn work(&self) -> PathBuf {
+        let mut path = self.folders.clone();
+        path.push("Work");
+
+        path
+    }
+
     pub fn songs(&self) -> PathBuf {
         let mut path = self.folders.clone();
         path.push("Songs");
@@ -74,7 +97,205 @@ pub struct Tokens {
     pub osu_client_id: u64,
     pub osu_client_secret: String,
     pub osu_api_key: String,
-    pub upload_secret: String,
+}
+
+#[derive(Debug)]
+pub struct QueueConfig {
+    /// How many replays can be rendered at the same time
+    pub workers: usize,
+    /// How many replays a single user can have in the queue
+    pub max_per_user: usize,
+}
+
+#[derive(Debug)]
+pub struct MapsetConfig {
+    /// How many bytes the songs folder may take up before mapsets are evicted
+    pub cache_size: u64,
+    /// How many bytes a downloaded mapset archive may have
+    pub max_download_size: u64,
+    /// Mirrors to download mapsets from, in the order they're tried
+    pub mirrors: Vec<MirrorConfig>,
+}
+
+#[derive(Debug)]
+pub struct JanitorConfig {
+    /// How often the janitor cleans up
+    pub interval: Duration,
+    /// How long replay files and videos of finished jobs are kept
+    pub retention: Duration,
+    /// How many bytes the Downloads folder may take up
+    pub downloads_quota: u64,
+    /// How many bytes the Replays folder may take up
+    pub replays_quota: u64,
+    /// How many bytes the Logs folder may take up
+    pub logs_quota: u64,
+    /// Owners are warned once less bytes than this are free on the disk
+    pub warn_free_space: u64,
+    /// New renders are refused once less bytes than this are free on the disk
+    pub critical_free_space: u64,
+}
+
+#[derive(Debug)]
+pub struct UploadConfig {
+    /// Backends to upload videos to, in the order they're tried
+    pub backends: Vec<UploadBackendConfig>,
+}
+
+#[derive(Debug)]
+pub enum UploadBackendConfig {
+    /// The multipart upload of shisha.mezo.xyz
+    Shisha { url: String, secret: String },
+    /// Copy videos into a folder that is served statically
+    Local {
+        dir: PathBuf,
+        /// Url under which the folder's files are available
+        public_url: String,
+    },
+    /// Any S3-compatible object store
+    S3 {
+        /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://localhost:9000`
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+        /// Url under which the bucket's objects are available
+        public_url: String,
+    },
+}
+
+impl UploadBackendConfig {
+    fn from_env(kind: UploadBackendKind) -> Result<Self> {
+        let backend = match kind {
+            UploadBackendKind::Shisha => Self::Shisha {
+                url: env_var("UPLOAD_URL")?,
+                secret: env_var("UPLOAD_SECRET")?,
+            },
+            UploadBackendKind::Local => Self::Local {
+                dir: env_var("UPLOAD_LOCAL_DIR")?,
+                public_url: env_var("UPLOAD_LOCAL_URL")?,
+            },
+            UploadBackendKind::S3 => {
+                let endpoint: String = env_var("S3_ENDPOINT")?;
+                let bucket: String = env_var("S3_BUCKET")?;
+                let default_url = format!("{}/{bucket}", endpoint.trim_end_matches('/'));
+
+                Self::S3 {
+                    public_url: env_var_or("S3_PUBLIC_URL", default_url)?,
+                    region: env_var_or("S3_REGION", "us-east-1".to_owned())?,
+                    access_key: env_var("S3_ACCESS_KEY")?,
+                    secret_key: env_var("S3_SECRET_KEY")?,
+                    endpoint,
+                    bucket,
+                }
+            }
+        };
+
+        Ok(backend)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum UploadBackendKind {
+    Shisha,
+    Local,
+    S3,
+}
+
+struct UploadBackends(Vec<UploadBackendKind>);
+
+impl Default for UploadBackends {
+    fn default() -> Self {
+        Self(vec![UploadBackendKind::Shisha])
+    }
+}
+
+/// Ratelimits are refilled in whole milliseconds so anything above this can't be kept
+const MAX_PER_SECOND: u32 = 1000;
+
+/// Rate limits and timeouts of the sites that the custom client requests
+#[derive(Debug)]
+pub struct ClientConfig {
+    pub attachments: SiteConfig,
+    pub osu_replays: SiteConfig,
+    pub upload: SiteConfig,
+}
+
+#[derive(Debug)]
+pub struct SiteConfig {
+    pub per_second: u32,
+    /// How long a single request may take until a response arrives
+    pub timeout: Duration,
+}
+
+impl SiteConfig {
+    fn from_env(
+        ratelimit_var: &'static str,
+        timeout_var: &'static str,
+        per_second: u32,
+        timeout_secs: u64,
+    ) -> Result<Self> {
+        let per_second: u32 = env_var_or(ratelimit_var, per_second)?;
+
+        if per_second == 0 {
+            bail!("env variable `{ratelimit_var}` must be greater than 0");
+        }
+
+        Ok(Self {
+            per_second: per_second.min(MAX_PER_SECOND),
+            timeout: Duration::from_secs(env_var_or(timeout_var, timeout_secs)?),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MirrorConfig {
+    /// Download url with `{mapset_id}` as placeholder
+    pub url: String,
+    pub per_second: u32,
+    pub timeout: Duration,
+}
+
+impl MirrorConfig {
+    const DEFAULT_PER_SECOND: u32 = 1;
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            per_second: Self::DEFAULT_PER_SECOND,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Parses `url[|requests per second[|timeout in seconds]]`
+    fn parse(s: &str) -> Option<Self> {
+        let mut split = s.split('|').map(str::trim);
+
+        let url = split.next().filter(|url| url.contains("{mapset_id}"))?;
+        let mut mirror = Self::new(url);
+
+        if let Some(per_second) = split.next() {
+            mirror.per_second = per_second
+                .parse::<u32>()
+                .ok()
+                .filter(|&n| n > 0)?
+                .min(MAX_PER_SECOND);
+        }
+
+        if let Some(timeout) = split.next() {
+            mirror.timeout = Duration::from_secs(timeout.parse().ok()?);
+        }
+
+        split.next().is_none().then_some(mirror)
+    }
+
+    fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("https://osu.direct/api/d/{mapset_id}"),
+            Self::new("https://chimu.moe/d/{mapset_id}"),
+        ]
+    }
 }
 
 #[derive(Debug)]
@@ -99,11 +320,10 @@ impl BotConfig {
                 osu_client_id: env_var("OSU_CLIENT_ID")?,
                 osu_client_secret: env_var("OSU_CLIENT_SECRET")?,
                 osu_api_key: env_var("OSU_API_KEY")?,
-                upload_secret: env_var("UPLOAD_SECRET")?,
             },
             paths: Paths {
-                danser: env_var("DANSER_PATH")?,
-                folders: env_var("FOLDERS_PATH")?,
+                danser: absolute_path(env_var("DANSER_PATH")?)?,
+                folders: absolute_path(env_var("FOLDERS_PATH")?)?,
             },
             emojis: Emojis {
                 man_running: env_var("MAN_RUNNING")?,
@@ -112,7 +332,48 @@ impl BotConfig {
             },
             owners: env_var("OWNERS_USER_ID")?,
             dev_guild: env_var("DEV_GUILD_ID")?,
-            upload_url: env_var("UPLOAD_URL")?,
+            upload: UploadConfig {
+                backends: env_var_or("UPLOAD_BACKENDS", UploadBackends::default())?
+                    .0
+                    .into_iter()
+                    .map(UploadBackendConfig::from_env)
+                    .collect::<Result<_>>()?,
+            },
+            queue: QueueConfig {
+                workers: env_var_or("RENDER_WORKERS", 1_usize)?.max(1),
+                max_per_user: env_var_or("MAX_QUEUED_PER_USER", 3_usize)?,
+            },
+            mapsets: MapsetConfig {
+                cache_size: env_var_or("MAPSET_CACHE_SIZE_MB", 10_240_u64)? * 1024 * 1024,
+                max_download_size: env_var_or("MAPSET_MAX_DOWNLOAD_MB", 200_u64)? * 1024 * 1024,
+                mirrors: env_var_or("MAPSET_MIRRORS", MirrorConfig::defaults())?,
+            },
+            client: ClientConfig {
+                attachments: SiteConfig::from_env(
+                    "ATTACHMENT_RATELIMIT",
+                    "ATTACHMENT_TIMEOUT_SECS",
+                    2,
+                    30,
+                )?,
+                osu_replays: SiteConfig::from_env(
+                    "OSU_REPLAY_RATELIMIT",
+                    "OSU_REPLAY_TIMEOUT_SECS",
+                    1,
+                    15,
+                )?,
+                upload: SiteConfig::from_env("UPLOAD_RATELIMIT", "UPLOAD_TIMEOUT_SECS", 1, 600)?,
+            },
+            janitor: JanitorConfig {
+                interval: Duration::from_secs(env_var_or("JANITOR_INTERVAL_SECS", 600_u64)?),
+                retention: Duration::from_secs(
+                    env_var_or("ARTIFACT_RETENTION_HOURS", 24_u64)? * 60 * 60,
+                ),
+                downloads_quota: env_var_or("DOWNLOADS_QUOTA_MB", 1024_u64)? * 1024 * 1024,
+                replays_quota: env_var_or("REPLAYS_QUOTA_MB", 20_480_u64)? * 1024 * 1024,
+                logs_quota: env_var_or("LOGS_QUOTA_MB", 512_u64)? * 1024 * 1024,
+                warn_free_space: env_var_or("DISK_WARN_FREE_MB", 10_240_u64)? * 1024 * 1024,
+                critical_free_space: env_var_or("DISK_CRITICAL_FREE_MB", 2048_u64)? * 1024 * 1024,
+            },
         };
 
         if CONFIG.set(config).is_err() {
@@ -145,7 +406,9 @@ macro_rules! env_kind {
 
 env_kind! {
     u16: s => { s.parse().ok() },
+    u32: s => { s.parse().ok() },
     u64: s => { s.parse().ok() },
+    usize: s => { s.parse().ok() },
     PathBuf: s => { s.parse().ok() },
     String: s => { Some(s.to_owned()) },
     Id<UserMarker>: s => { s.parse().ok().map(Id::new) },
@@ -158,6 +421,27 @@ env_kind! {
             .collect::<Result<Vec<_>, _>>()
             .ok()
     },
+    UploadBackends: s => {
+        s.split(',')
+            .map(str::trim)
+            .map(|backend| match backend {
+                "shisha" => Some(UploadBackendKind::Shisha),
+                "local" => Some(UploadBackendKind::Local),
+                "s3" => Some(UploadBackendKind::S3),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|backends| !backends.is_empty())
+            .map(UploadBackends)
+    },
+    Vec<MirrorConfig>: s => {
+        s.split(',')
+            .map(str::trim)
+            .filter(|mirror| !mirror.is_empty())
+            .map(MirrorConfig::parse)
+            .collect::<Option<Vec<_>>>()
+            .filter(|mirrors| !mirrors.is_empty())
+    },
 }
 
 fn env_var<T: EnvKind>(name: &'static str) -> Result<T> {
@@ -170,3 +454,22 @@ fn env_var<T: EnvKind>(name: &'static str) -> Result<T> {
         )
     })
 }
+
+/// danser runs in its own working directory so relative paths would no longer resolve
+fn absolute_path(path: PathBuf) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path);
+    }
+
+    let current_dir = env::current_dir().context("failed to get current directory")?;
+
+    Ok(current_dir.join(path))
+}
+
+/// Same as [`env_var`] but uses the default if the variable is not set
+fn env_var_or<T: EnvKind>(name: &'static str, default: T) -> Result<T> {
+    match env::var(name) {
+        Ok(_) => env_var(name),
+        Err(_) => Ok(default),
+    }
+}
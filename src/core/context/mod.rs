@@ -20,7 +20,10 @@ use crate::{
     util::hasher::IntBuildHasher,
 };
 
-use super::{cluster::build_cluster, settings::RootSettings, stats::BotStats, Cache, ReplayQueue};
+use super::{
+    cluster::build_cluster, settings::RootSettings, stats::BotStats, Cache, Janitor, MapsetCache,
+    ReplayQueue,
+};
 
 use self::skin_list::SkinList;
 
@@ -35,6 +38,8 @@ pub struct Context {
     pub standby: Standby,
     pub stats: Arc<BotStats>,
     pub replay_queue: ReplayQueue,
+    pub mapsets: MapsetCache,
+    pub janitor: Janitor,
     root_settings: RootSettings,
     skin_list: Arc<Mutex<SkinList>>,
     application_id: Id<ApplicationMarker>,
@@ -118,6 +123,10 @@ impl Context {
         let (cluster, events) =
             build_cluster(discord_token, Arc::clone(&http), resume_data).await?;
 
+        let mapsets = MapsetCache::new(config.mapsets.cache_size)
+            .await
+            .context("failed to create mapset cache")?;
+
         let paginations = TokioMutexMap::with_shard_amount_and_hasher(16, IntBuildHasher);
 
         let ctx = Self {
@@ -130,7 +139,9 @@ impl Context {
             paginations: Arc::new(paginations),
             standby: Standby::new(),
             stats,
-            replay_queue: ReplayQueue::new(),
+            replay_queue: ReplayQueue::new(config.queue.workers),
+            mapsets,
+            janitor: Janitor::default(),
             skin_list: Arc::new(Mutex::default()),
         };
 
@@ -155,6 +166,10 @@ async fn create_missing_folders_and_files(config: &BotConfig) -> Result<()> {
         .await
         .context("failed to create Downloads folder")?;
 
+    fs::create_dir_all(config.paths.logs())
+        .await
+        .context("failed to create Logs folder")?;
+
     fs::create_dir_all(config.paths.replays())
         .await
         .context("failed to create Replays folder")?;
@@ -163,6 +178,10 @@ async fn create_missing_folders_and_files(config: &BotConfig) -> Result<()> {
         .await
         .context("failed to create Skins folder")?;
 
+    fs::create_dir_all(config.paths.work())
+        .await
+        .context("failed to create Work folder")?;
+
     fs::create_dir_all(config.paths.songs())
         .await
         .context("failed to create Songs folder")?;
@@ -33,6 +33,8 @@ pub async fn handle_component(ctx: Arc<Context>, mut component: InteractionCompo
         "profile_compact" => handle_profile_compact(ctx, component).await,
         "profile_medium" => handle_profile_medium(ctx, component).await,
         "profile_full" => handle_profile_full(ctx, component).await,
+        // Handled through the standby while `/render` waits for it
+        "render_force" => return,
         _ => return error!("unknown message component `{name}`"),
     };
 
@@ -0,0 +1,371 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use eyre::{Context as _, Report, Result};
+use tokio::{fs, task, time};
+
+use crate::{
+    core::{BotConfig, Context},
+    util::ChannelExt,
+};
+
+/// Files that were modified more recently than this are never removed
+/// since they might still be written to, e.g. a video while danser renders it
+const MIN_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// How much space is left on the disk
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+enum DiskState {
+    Ok = 0,
+    /// Below the warning threshold
+    Low = 1,
+    /// Below the critical threshold, no new renders are accepted
+    Critical = 2,
+}
+
+impl DiskState {
+    fn from_u8(state: u8) -> Self {
+        match state {
+            0 => Self::Ok,
+            1 => Self::Low,
+            _ => Self::Critical,
+        }
+    }
+}
+
+/// Removes leftover files of finished jobs and keeps an eye on the free disk space
+#[derive(Default)]
+pub struct Janitor {
+    /// The current [`DiskState`]
+    disk: AtomicU8,
+}
+
+impl Janitor {
+    /// Whether new renders should be refused
+    pub fn is_disk_critical(&self) -> bool {
+        DiskState::from_u8(self.disk.load(Ordering::Relaxed)) == DiskState::Critical
+    }
+
+    /// Clean up periodically in the background
+    pub fn process(ctx: Arc<Context>) {
+        tokio::spawn(async move {
+            let mut interval = time::interval(BotConfig::get().janitor.interval);
+
+            loop {
+                interval.tick().await;
+
+                if let Err(err) = clean_up(&ctx).await {
+                    warn!("{:?}", err.wrap_err("janitor failed to clean up"));
+                }
+
+                if let Err(err) = check_disk(&ctx).await {
+                    warn!("{:?}", err.wrap_err("janitor failed to check disk space"));
+                }
+            }
+        });
+    }
+}
+
+async fn clean_up(ctx: &Context) -> Result<()> {
+    let config = BotConfig::get();
+    let protected = protected_paths(ctx).await;
+
+    let folders = [
+        (config.paths.downloads(), config.janitor.downloads_quota),
+        (config.paths.replays(), config.janitor.replays_quota),
+        (config.paths.logs(), config.janitor.logs_quota),
+    ];
+
+    for (dir, quota) in folders {
+        let (removed, freed) = clean_folder(&dir, quota, config.janitor.retention, &protected)
+            .await
+            .with_context(|| format!("failed to clean up {dir:?}"))?;
+
+        if removed > 0 {
+            info!(
+                "Janitor removed {removed} file(s) in {dir:?}, freeing {} MB",
+                freed / 1024 / 1024
+            );
+        }
+    }
+
+    let work_dir = config.paths.work();
+
+    clean_work_dirs(&work_dir, &protected)
+        .await
+        .with_context(|| format!("failed to clean up {work_dir:?}"))?;
+
+    Ok(())
+}
+
+/// Remove working directories and mapset extractions that were left behind by renders
+/// that never finished, e.g. because the bot was shut down while danser was running
+async fn clean_work_dirs(dir: &Path, protected: &HashSet<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("failed to read dir at {dir:?}"))?;
+
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .with_context(|| format!("failed to read entry in {dir:?}"))?
+    {
+        let path = entry.path();
+
+        if protected.contains(&path) {
+            continue;
+        }
+
+        // A render might have started since the protected paths were collected
+        let is_stale = match entry.metadata().await {
+            Ok(metadata) => {
+                metadata.is_dir()
+                    && metadata
+                        .modified()
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .map_or(false, |age| age >= MIN_AGE)
+            }
+            Err(_) => false,
+        };
+
+        if !is_stale {
+            continue;
+        }
+
+        if let Err(err) = fs::remove_dir_all(&path).await {
+            let context = format!("failed to remove working directory at {path:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+        } else {
+            info!("Janitor removed leftover working directory {path:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Files that still belong to a queued, active, or pending job
+async fn protected_paths(ctx: &Context) -> HashSet<PathBuf> {
+    let waiting: Vec<_> = ctx
+        .replay_queue
+        .queue
+        .lock()
+        .await
+        .iter()
+        .cloned()
+        .collect();
+    let active = ctx.replay_queue.active().await;
+
+    let jobs = waiting
+        .into_iter()
+        .chain(active.into_iter().map(|active| active.data))
+        .flat_map(|data| {
+            [
+                data.path,
+                data.id.video_path(),
+                data.id.log_path(),
+                data.id.work_dir(),
+            ]
+        });
+
+    let pending = ctx.replay_queue.pending_uploads.video_paths().await;
+    let downloads = ctx.mapsets.download_paths();
+
+    jobs.chain(pending).chain(downloads).collect()
+}
+
+/// Remove files that are older than the retention period, then the oldest
+/// remaining ones until the folder fits into its quota.
+///
+/// Returns how many files were removed and how many bytes were freed.
+async fn clean_folder(
+    dir: &Path,
+    quota: u64,
+    retention: Duration,
+    protected: &HashSet<PathBuf>,
+) -> Result<(usize, u64)> {
+    let mut entries = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("failed to read dir at {dir:?}"))?;
+
+    let now = SystemTime::now();
+    let mut total_size = 0;
+    let mut removable = Vec::new();
+
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .with_context(|| format!("failed to read entry in {dir:?}"))?
+    {
+        let metadata = match entry.metadata().await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => continue,
+            Err(err) => {
+                let context = format!("failed to read metadata of {:?}", entry.path());
+                warn!("{:?}", Report::from(err).wrap_err(context));
+
+                continue;
+            }
+        };
+
+        let size = metadata.len();
+        total_size += size;
+
+        let path = entry.path();
+
+        if protected.contains(&path) {
+            continue;
+        }
+
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+
+        if age >= MIN_AGE {
+            removable.push((path, size, age));
+        }
+    }
+
+    // Oldest first
+    removable.sort_unstable_by(|(_, _, a), (_, _, b)| b.cmp(a));
+
+    let mut removed = 0;
+    let mut freed = 0;
+
+    for (path, size, age) in removable {
+        if age < retention && total_size <= quota {
+            break;
+        }
+
+        match fs::remove_file(&path).await {
+            Ok(_) => {
+                removed += 1;
+                freed += size;
+                total_size -= size;
+            }
+            Err(err) => {
+                let context = format!("failed to remove file at {path:?}");
+                warn!("{:?}", Report::from(err).wrap_err(context));
+            }
+        }
+    }
+
+    if total_size > quota {
+        warn!(
+            "{dir:?} still takes up {} MB after cleaning up, the quota is {} MB",
+            total_size / 1024 / 1024,
+            quota / 1024 / 1024
+        );
+    }
+
+    Ok((removed, freed))
+}
+
+/// Update the disk state and notify the owners if it got worse
+async fn check_disk(ctx: &Context) -> Result<()> {
+    let config = BotConfig::get();
+    let folders = config.paths.folders().to_owned();
+
+    let free = task::spawn_blocking(move || free_space(&folders))
+        .await
+        .context("failed to join free space check")??;
+
+    let state = if free < config.janitor.critical_free_space {
+        DiskState::Critical
+    } else if free < config.janitor.warn_free_space {
+        DiskState::Low
+    } else {
+        DiskState::Ok
+    };
+
+    let prev = DiskState::from_u8(ctx.janitor.disk.swap(state as u8, Ordering::Relaxed));
+
+    if state == prev {
+        return Ok(());
+    }
+
+    let free_mb = free / 1024 / 1024;
+
+    let content = match state {
+        DiskState::Ok => {
+            info!("Free disk space is back to {free_mb} MB");
+
+            return Ok(());
+        }
+        DiskState::Low if prev == DiskState::Critical => {
+            info!("Free disk space is back to {free_mb} MB, accepting renders again");
+
+            return Ok(());
+        }
+        DiskState::Low => format!("Free disk space is running low, only {free_mb} MB are left"),
+        DiskState::Critical => format!(
+            "Free disk space is critically low, only {free_mb} MB are left. \
+            New renders are refused until space is freed up."
+        ),
+    };
+
+    warn!("{content}");
+
+    for &owner in config.owners.iter() {
+        let res = match ctx.http.create_private_channel(owner).exec().await {
+            Ok(response) => response.model().await.map_err(Report::from),
+            Err(err) => Err(Report::from(err)),
+        };
+
+        let channel = match res {
+            Ok(channel) => channel.id,
+            Err(err) => {
+                let context = format!("failed to create DM channel with owner {owner}");
+                warn!("{:?}", err.wrap_err(context));
+
+                continue;
+            }
+        };
+
+        if let Err(err) = channel.plain_message(ctx, &content).await {
+            let context = format!("failed to warn owner {owner} about disk space");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+        }
+    }
+
+    Ok(())
+}
+
+/// Bytes that are available to unprivileged users on the disk containing the path
+#[cfg(unix)]
+fn free_space(path: &Path) -> Result<u64> {
+    use std::{ffi::CString, io::Error as IoError, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("invalid path {path:?}"))?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        let err = IoError::last_os_error();
+
+        return Err(Report::from(err).wrap_err(format!("failed to get disk stats of {path:?}")));
+    }
+
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+
+    Ok(free)
+}
+
+/// Free disk space is only checked on unix, other platforms always have enough
+#[cfg(not(unix))]
+fn free_space(_: &Path) -> Result<u64> {
+    Ok(u64::MAX)
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use eyre::{Context as _, Result};
+use tokio::{
+    sync::{Mutex as TokioMutex, Notify, OwnedMutexGuard},
+    task,
+};
+
+use crate::core::BotConfig;
+
+/// Keeps track of the extracted mapsets in the songs folder
+/// and evicts the least recently used ones once they
+/// take up more space than the configured budget.
+pub struct MapsetCache {
+    inner: Mutex<CacheInner>,
+    /// Locks of mapsets that are currently being checked or downloaded
+    downloads: Mutex<HashMap<u32, Arc<TokioMutex<()>>>>,
+    /// Notified whenever a lease is dropped
+    released: Notify,
+    /// Archives and extraction folders of downloads that are in progress
+    downloading: Mutex<HashSet<PathBuf>>,
+    budget: u64,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    /// Mapsets that are extracted in the songs folder
+    mapsets: HashMap<u32, CachedMapset>,
+    /// How many replays currently use a mapset, whether it's extracted yet or not
+    leases: HashMap<u32, usize>,
+    total_size: u64,
+}
+
+struct CachedMapset {
+    size: u64,
+    last_used: SystemTime,
+}
+
+impl MapsetCache {
+    /// Create the cache based on the mapsets that are already on disk
+    pub async fn new(budget: u64) -> Result<Self> {
+        let songs = BotConfig::get().paths.songs();
+
+        let inner = task::spawn_blocking(move || scan_songs(&songs))
+            .await
+            .context("failed to join songs scan")?
+            .context("failed to scan songs folder")?;
+
+        info!(
+            "Found {} cached mapsets taking up {}MB",
+            inner.mapsets.len(),
+            inner.total_size / 1024 / 1024
+        );
+
+        Ok(Self {
+            inner: Mutex::new(inner),
+            downloads: Mutex::default(),
+            released: Notify::new(),
+            downloading: Mutex::default(),
+            budget,
+        })
+    }
+
+    /// Wait until no other worker checks or downloads the mapset.
+    ///
+    /// Workers rendering the same mapset concurrently must hold this
+    /// while looking into the mapset's folder so they don't download
+    /// into or replace the folder at the same time.
+    pub async fn lock_download(&self, mapset_id: u32) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut downloads = self.downloads.lock().unwrap();
+
+            // Forget the locks that nobody is holding or waiting for
+            downloads.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+            Arc::clone(downloads.entry(mapset_id).or_default())
+        };
+
+        lock.lock_owned().await
+    }
+
+    /// Lock the mapset's download lock only if nobody is holding it.
+    fn try_lock_download(&self, mapset_id: u32) -> Option<OwnedMutexGuard<()>> {
+        let lock = {
+            let mut downloads = self.downloads.lock().unwrap();
+
+            Arc::clone(downloads.entry(mapset_id).or_default())
+        };
+
+        lock.try_lock_owned().ok()
+    }
+
+    /// Keep the janitor from removing the files of a download until the guard is dropped
+    pub fn protect_download(&self, paths: Vec<PathBuf>) -> ProtectedDownload<'_> {
+        self.downloading
+            .lock()
+            .unwrap()
+            .extend(paths.iter().cloned());
+
+        ProtectedDownload { cache: self, paths }
+    }
+
+    /// Files of downloads that are in progress
+    pub fn download_paths(&self) -> Vec<PathBuf> {
+        self.downloading.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Mark the mapset as used so that it won't be evicted until the lease is dropped
+    pub fn lease(&self, mapset_id: u32) -> MapsetLease<'_> {
+        let mut inner = self.inner.lock().unwrap();
+
+        *inner.leases.entry(mapset_id).or_default() += 1;
+
+        if let Some(mapset) = inner.mapsets.get_mut(&mapset_id) {
+            mapset.last_used = SystemTime::now();
+        }
+
+        MapsetLease {
+            cache: self,
+            mapset_id,
+        }
+    }
+
+    /// Update the size of a freshly extracted or removed mapset
+    /// and evict other mapsets if the budget is exceeded.
+    pub async fn update_size(&self, mapset_id: u32) {
+        let path = mapset_path(mapset_id);
+
+        let size = match task::spawn_blocking(move || dir_size(&path)).await {
+            Ok(Ok(size)) => size,
+            Ok(Err(err)) if err.kind() == io::ErrorKind::NotFound => 0,
+            Ok(Err(err)) => {
+                warn!("failed to get size of mapset {mapset_id}: {err}");
+
+                return;
+            }
+            Err(err) => {
+                warn!("failed to join mapset size task: {err}");
+
+                return;
+            }
+        };
+
+        let candidates = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.set_size(mapset_id, size);
+
+            inner.eviction_candidates(self.budget)
+        };
+
+        let mut evicted = 0;
+
+        for mapset_id in candidates {
+            // A worker holding the lock is about to use the mapset so it's skipped.
+            // Waiting for the lock instead could deadlock with that worker's eviction.
+            let _download_guard = match self.try_lock_download(mapset_id) {
+                Some(guard) => guard,
+                None => continue,
+            };
+
+            {
+                let mut inner = self.inner.lock().unwrap();
+
+                if inner.total_size <= self.budget {
+                    break;
+                }
+
+                // The mapset might have been leased since the candidates were picked
+                if !inner.remove_unused(mapset_id) {
+                    continue;
+                }
+            }
+
+            let path = mapset_path(mapset_id);
+
+            match tokio::fs::remove_dir_all(&path).await {
+                Ok(_) => evicted += 1,
+                Err(err) => warn!("failed to remove evicted mapset at {path:?}: {err}"),
+            }
+        }
+
+        if evicted > 0 {
+            let total_size = self.inner.lock().unwrap().total_size;
+
+            info!(
+                "Evicted {evicted} mapsets, cache now takes up {}MB",
+                total_size / 1024 / 1024
+            );
+        }
+    }
+
+    fn release(&self, mapset_id: u32) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+
+            if let Some(leases) = inner.leases.get_mut(&mapset_id) {
+                *leases -= 1;
+
+                if *leases == 0 {
+                    inner.leases.remove(&mapset_id);
+                }
+            }
+
+            if let Some(mapset) = inner.mapsets.get_mut(&mapset_id) {
+                mapset.last_used = SystemTime::now();
+            }
+        }
+
+        self.released.notify_waiters();
+    }
+
+    fn in_use(&self, mapset_id: u32) -> usize {
+        let inner = self.inner.lock().unwrap();
+
+        inner.leases.get(&mapset_id).copied().unwrap_or(0)
+    }
+}
+
+impl CacheInner {
+    /// Track the new size of a mapset, forgetting it if its folder is gone or empty.
+    fn set_size(&mut self, mapset_id: u32, size: u64) {
+        let prev = if size == 0 {
+            self.mapsets.remove(&mapset_id)
+        } else {
+            let mapset = CachedMapset {
+                size,
+                last_used: SystemTime::now(),
+            };
+
+            self.mapsets.insert(mapset_id, mapset)
+        };
+
+        let prev = prev.map_or(0, |mapset| mapset.size);
+        self.total_size = self.total_size - prev + size;
+    }
+
+    /// The least recently used mapsets that are not in use, oldest first,
+    /// or nothing if the total size already fits into the budget.
+    fn eviction_candidates(&self, budget: u64) -> Vec<u32> {
+        if self.total_size <= budget {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<_> = self
+            .mapsets
+            .iter()
+            .filter(|(id, _)| !self.leases.contains_key(id))
+            .map(|(id, mapset)| (*id, mapset.last_used))
+            .collect();
+
+        candidates.sort_unstable_by_key(|(_, last_used)| *last_used);
+
+        candidates.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// Stop tracking the mapset unless it's in use.
+    ///
+    /// Returns whether the mapset was removed so its folder can be deleted.
+    fn remove_unused(&mut self, mapset_id: u32) -> bool {
+        if self.leases.contains_key(&mapset_id) {
+            return false;
+        }
+
+        match self.mapsets.remove(&mapset_id) {
+            Some(mapset) => {
+                self.total_size -= mapset.size;
+
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Keeps a mapset from being evicted while it's being used
+pub struct MapsetLease<'c> {
+    cache: &'c MapsetCache,
+    mapset_id: u32,
+}
+
+impl MapsetLease<'_> {
+    /// Wait until no other replay uses the mapset anymore so its folder can be modified.
+    ///
+    /// Only call this while holding the mapset's [`MapsetCache::lock_download`],
+    /// otherwise other workers might lease the mapset again right away.
+    pub async fn wait_exclusive(&self) {
+        loop {
+            // Register before checking so no release is missed in between
+            let released = self.cache.released.notified();
+
+            if self.cache.in_use(self.mapset_id) <= 1 {
+                return;
+            }
+
+            released.await;
+        }
+    }
+}
+
+impl Drop for MapsetLease<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.cache.release(self.mapset_id);
+    }
+}
+
+/// Keeps the files of a download from being removed by the janitor
+pub struct ProtectedDownload<'c> {
+    cache: &'c MapsetCache,
+    paths: Vec<PathBuf>,
+}
+
+impl Drop for ProtectedDownload<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        let mut downloading = self.cache.downloading.lock().unwrap();
+
+        for path in self.paths.iter() {
+            downloading.remove(path);
+        }
+    }
+}
+
+pub fn mapset_path(mapset_id: u32) -> PathBuf {
+    let mut path = BotConfig::get().paths.songs();
+    path.push(mapset_id.to_string());
+
+    path
+}
+
+fn scan_songs(songs: &Path) -> io::Result<CacheInner> {
+    let mut inner = CacheInner::default();
+
+    let entries = match fs::read_dir(songs) {
+        Ok(entries) => entries,
+        // Nothing was downloaded yet
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(inner),
+        Err(err) => return Err(err),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+
+        let mapset_id = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(mapset_id) => mapset_id,
+            None => continue,
+        };
+
+        let path = entry.path();
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        let size = dir_size(&path)?;
+        let last_used = entry.metadata()?.modified()?;
+
+        let mapset = CachedMapset { size, last_used };
+
+        inner.mapsets.insert(mapset_id, mapset);
+        inner.total_size += size;
+    }
+
+    Ok(inner)
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+
+    Ok(size)
+}
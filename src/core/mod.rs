@@ -1,9 +1,11 @@
 pub use self::{
     cache::Cache,
-    config::BotConfig,
+    config::{BotConfig, ClientConfig, MirrorConfig, SiteConfig, UploadBackendConfig},
     context::Context,
     events::event_loop,
-    replay_queue::{ReplayData, ReplayQueue, ReplayStatus, TimePoints},
+    janitor::Janitor,
+    mapset_cache::MapsetCache,
+    replay_queue::{ActiveReplay, ReplayData, ReplayQueue, ReplayStatus, TimePoints},
 };
 
 mod cache;
@@ -11,9 +13,11 @@ mod cluster;
 mod config;
 mod context;
 mod events;
+mod janitor;
 
 pub mod commands;
 pub mod logging;
+pub mod mapset_cache;
 pub mod replay_queue;
 pub mod settings;
 pub mod stats;
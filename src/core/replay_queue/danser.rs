@@ -0,0 +1,353 @@
+use std::{
+    collections::VecDeque,
+    error::Error as StdError,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{Error as IoError, Result as IoResult},
+    path::Path,
+    process::{Command as StdCommand, ExitStatus},
+    time::Duration,
+};
+
+use eyre::{Context as _, Result};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    process::{Child, ChildStdout, Command},
+    time::sleep,
+};
+
+use crate::core::{Context, ReplayStatus};
+
+use super::{Cancellation, JobId, ProgressTracker, ReplayData};
+
+/// Time danser gets regardless of the replay's length
+const TIMEOUT_BASE: Duration = Duration::from_secs(5 * 60);
+
+/// Additional seconds danser gets per second of the rendered video
+const TIMEOUT_PER_SECOND: f32 = 10.0;
+
+/// Amount of stdout lines that are kept to classify errors
+const STDOUT_TAIL: usize = 50;
+
+type DanserLog = BufWriter<File>;
+
+/// How long danser may run for the given replay before it's killed
+pub fn danser_timeout(data: &ReplayData) -> Duration {
+    TIMEOUT_BASE + Duration::from_secs_f32(data.duration() * TIMEOUT_PER_SECOND)
+}
+
+#[derive(Debug)]
+pub enum DanserError {
+    Cancelled,
+    Ffmpeg,
+    InvalidReplay,
+    MissingMap,
+    MissingVideo,
+    Spawn(IoError),
+    Status(ExitStatus),
+    Timeout(Duration),
+    Wait(IoError),
+}
+
+impl DanserError {
+    /// Explanation for the user whose replay failed
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Self::Cancelled => "The replay was cancelled",
+            Self::Ffmpeg => "Failed to encode the video",
+            Self::InvalidReplay => "danser did not like the replay file",
+            Self::MissingMap => "danser could not find the map of the replay",
+            Self::MissingVideo | Self::Status(_) => "danser failed to render the replay",
+            Self::Spawn(_) | Self::Wait(_) => "Failed to run danser on the replay",
+            Self::Timeout(_) => "Rendering took too long and was stopped",
+        }
+    }
+
+    /// Check danser's output for known errors
+    fn classify(stderr: &str, stdout: &VecDeque<String>) -> Option<Self> {
+        let lines = stderr
+            .lines()
+            .chain(stdout.iter().map(String::as_str))
+            .map(str::to_ascii_lowercase);
+
+        for line in lines {
+            if line.contains("ffmpeg") && (line.contains("error") || line.contains("failed")) {
+                return Some(Self::Ffmpeg);
+            } else if line.contains("beatmap not found") || line.contains("couldn't find beatmap") {
+                return Some(Self::MissingMap);
+            } else if line.contains("replay")
+                && (line.contains("invalid") || line.contains("corrupt"))
+            {
+                return Some(Self::InvalidReplay);
+            }
+        }
+
+        None
+    }
+}
+
+impl Display for DanserError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Cancelled => f.write_str("danser was cancelled"),
+            Self::Ffmpeg => f.write_str("ffmpeg failed to encode the video"),
+            Self::InvalidReplay => f.write_str("danser could not parse the replay"),
+            Self::MissingMap => f.write_str("danser could not find the map"),
+            Self::MissingVideo => f.write_str("danser finished without creating a video"),
+            Self::Spawn(_) => f.write_str("failed to spawn danser"),
+            Self::Status(status) => write!(f, "danser exited with {status}"),
+            Self::Timeout(timeout) => write!(f, "danser timed out after {timeout:?}"),
+            Self::Wait(_) => f.write_str("failed to wait for danser"),
+        }
+    }
+}
+
+impl StdError for DanserError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Spawn(err) | Self::Wait(err) => Some(err),
+            Self::Cancelled
+            | Self::Ffmpeg
+            | Self::InvalidReplay
+            | Self::MissingMap
+            | Self::MissingVideo
+            | Self::Status(_)
+            | Self::Timeout(_) => None,
+        }
+    }
+}
+
+/// Run danser in the job's own working directory until it finishes,
+/// is cancelled, or runs into the timeout.
+///
+/// All paths of the command must be absolute since they would otherwise resolve from the
+/// working directory. danser's settings are still shared between jobs since danser only
+/// reads them and can't be pointed to a settings file outside of its own folder.
+///
+/// danser's output is captured in the job's log file which is only kept if the render failed.
+pub async fn run_danser(
+    ctx: &Context,
+    progress: &mut ProgressTracker,
+    mut command: StdCommand,
+    cancel: &Cancellation,
+    timeout: Duration,
+    job: JobId,
+) -> Result<(), DanserError> {
+    let work_dir = job.work_dir();
+    let log_path = job.log_path();
+
+    fs::create_dir_all(&work_dir)
+        .await
+        .map_err(DanserError::Spawn)?;
+
+    let log_file = File::create(&log_path).await.map_err(DanserError::Spawn)?;
+    let mut log = BufWriter::new(log_file);
+
+    command.current_dir(&work_dir);
+    let video = job.video_path();
+    let res = spawn_danser(ctx, progress, command, cancel, timeout, &video, &mut log).await;
+
+    if let Err(err) = log.flush().await {
+        warn!("failed to flush danser log at {log_path:?}: {err}");
+    }
+
+    drop(log);
+
+    if let Err(err) = fs::remove_dir_all(&work_dir).await {
+        warn!("failed to remove danser working directory at {work_dir:?}: {err}");
+    }
+
+    // Only failed renders are worth looking into later
+    if matches!(res, Ok(_) | Err(DanserError::Cancelled)) {
+        if let Err(err) = fs::remove_file(&log_path).await {
+            warn!("failed to remove danser log at {log_path:?}: {err}");
+        }
+    }
+
+    res
+}
+
+/// Run danser until it finishes, is cancelled, or runs into the timeout.
+///
+/// danser is started in its own process group so that
+/// its ffmpeg child process can be killed along with it.
+async fn spawn_danser(
+    ctx: &Context,
+    progress: &mut ProgressTracker,
+    mut command: StdCommand,
+    cancel: &Cancellation,
+    timeout: Duration,
+    video: &Path,
+    log: &mut DanserLog,
+) -> Result<(), DanserError> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let mut command = Command::from(command);
+    command.kill_on_drop(true);
+
+    let mut child = command.spawn().map_err(DanserError::Spawn)?;
+
+    let stdout = child.stdout.take().expect("missing stdout on child");
+    let mut stderr = child.stderr.take().expect("missing stderr on child");
+
+    // Read stderr in the background so danser can't block on a full pipe
+    let stderr_handle = tokio::spawn(async move {
+        let mut res = String::new();
+        let _ = stderr.read_to_string(&mut res).await;
+
+        res
+    });
+
+    let mut stdout_tail = VecDeque::with_capacity(STDOUT_TAIL);
+
+    let status = tokio::select! {
+        status = wait_for_danser(ctx, progress, &mut child, stdout, &mut stdout_tail, log) => {
+            status.map_err(DanserError::Wait)?
+        }
+        _ = cancel.cancelled() => {
+            kill_danser(&mut child).await;
+
+            return Err(DanserError::Cancelled);
+        }
+        _ = sleep(timeout) => {
+            kill_danser(&mut child).await;
+
+            return Err(DanserError::Timeout(timeout));
+        }
+    };
+
+    let stderr = stderr_handle.await.unwrap_or_default();
+
+    if !stderr.is_empty() {
+        warn!("danser stderr: {stderr}");
+
+        let _ = log.write_all(b"--- stderr ---\n").await;
+        let _ = log.write_all(stderr.as_bytes()).await;
+    }
+
+    if status.success() && video.exists() {
+        return Ok(());
+    }
+
+    if let Some(err) = DanserError::classify(&stderr, &stdout_tail) {
+        return Err(err);
+    }
+
+    if status.success() {
+        Err(DanserError::MissingVideo)
+    } else {
+        Err(DanserError::Status(status))
+    }
+}
+
+/// Wait for danser to exit while keeping track of its progress
+async fn wait_for_danser(
+    ctx: &Context,
+    progress: &mut ProgressTracker,
+    child: &mut Child,
+    stdout: ChildStdout,
+    stdout_tail: &mut VecDeque<String>,
+    log: &mut DanserLog,
+) -> IoResult<ExitStatus> {
+    let reader = BufReader::new(stdout);
+    let read_fut = read_danser_progress(ctx, progress, reader, stdout_tail, log);
+    let (_, status) = tokio::join!(read_fut, child.wait());
+
+    status
+}
+
+async fn kill_danser(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // The process group id is the pid of its leader
+        if unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+            let err = IoError::last_os_error();
+            warn!("failed to kill danser's process group: {err}");
+        }
+    }
+
+    if let Err(err) = child.kill().await {
+        warn!("failed to kill danser: {err}");
+    }
+}
+
+async fn read_danser_progress(
+    ctx: &Context,
+    progress: &mut ProgressTracker,
+    reader: BufReader<ChildStdout>,
+    tail: &mut VecDeque<String>,
+    log: &mut DanserLog,
+) {
+    async fn inner(
+        ctx: &Context,
+        progress: &mut ProgressTracker,
+        reader: BufReader<ChildStdout>,
+        tail: &mut VecDeque<String>,
+        log: &mut DanserLog,
+    ) -> Result<()> {
+        let worker = progress.worker();
+        let mut lines = reader.lines();
+        let mut started_encoding = false;
+
+        trace!("Start reading danser logs...");
+
+        loop {
+            trace!("Next danser log line...");
+
+            let line_opt = lines
+                .next_line()
+                .await
+                .context("failed to read line of danser's stdout")?;
+
+            let line = match line_opt {
+                Some(line) => line,
+                None => return Ok(()),
+            };
+
+            // The log is only for debugging, failing to write it shouldn't stop the render
+            let _ = log.write_all(line.as_bytes()).await;
+            let _ = log.write_all(b"\n").await;
+
+            let trimmed_line = line.trim_end();
+
+            if !trimmed_line.is_empty() {
+                debug!("[DANSER {worker}]: {trimmed_line}");
+            }
+
+            if let Some(idx) = line.find("Progress: ").map(|idx| idx + 10) {
+                if let Some(end) = line[idx..].find('%') {
+                    if let Ok(percent) = line[idx..idx + end].parse() {
+                        let status = if started_encoding {
+                            ReplayStatus::Encoding(percent)
+                        } else {
+                            ReplayStatus::Rendering(percent)
+                        };
+
+                        progress.set_status(ctx, status).await;
+                    } else {
+                        debug!("failed to parse progress in line `{line}`");
+                    }
+                }
+            } else if line.contains("Starting encoding!") {
+                started_encoding = true;
+                let status = ReplayStatus::Encoding(0);
+                progress.set_status(ctx, status).await;
+            }
+
+            if tail.len() == STDOUT_TAIL {
+                tail.pop_front();
+            }
+
+            tail.push_back(line);
+        }
+    }
+
+    if let Err(err) = inner(ctx, progress, reader, tail, log).await {
+        error!("{err:?}");
+    }
+
+    trace!("Stopped reading danser logs");
+}
@@ -1,52 +1,102 @@
-use std::{borrow::Cow, path::PathBuf};
+use std::{
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
+use eyre::Report;
 use osu_db::Replay;
+use rosu_v2::prelude::GameMods;
+use tokio::{fs, sync::Notify};
 use twilight_model::id::{
-    marker::{ChannelMarker, UserMarker},
+    marker::{ChannelMarker, GuildMarker, UserMarker},
     Id,
 };
 
-use crate::util::CowUtils;
+use super::{Cancelled, JobId, ProgressMessage, Requester};
+
+/// Assumed map length in seconds if the actual length is unknown
+const DEFAULT_MAP_LENGTH: u32 = 180;
 
 #[derive(Clone)]
 pub struct ReplayData {
+    pub id: JobId,
+    /// Human-friendly name of the replay
+    pub name: String,
+    pub guild: Option<Id<GuildMarker>>,
     pub input_channel: Id<ChannelMarker>,
+    /// Total length of the map in seconds, if known
+    pub map_length: Option<u32>,
     pub output_channel: Id<ChannelMarker>,
     pub pitch: Option<f64>,
     pub path: PathBuf,
+    /// Message to show the replay's progress in, if any
+    pub progress: Option<ProgressMessage>,
     pub replay: ReplaySlim,
     pub time_points: TimePoints,
     pub user: Id<UserMarker>,
 }
 
 impl ReplayData {
-    pub fn replay_name(&self) -> Cow<'_, str> {
-        let name = self
-            .path
-            .file_name()
-            .expect("missing file name")
-            .to_string_lossy();
-
-        let extension = name.rfind(".osr").unwrap_or(name.len());
-        let suffix = name[..extension].rfind("_Osu").unwrap_or(extension);
-
-        match name {
-            Cow::Borrowed(name) => name[..suffix].cow_replace('_', " "),
-            Cow::Owned(mut name) => {
-                name.truncate(suffix);
-
-                let mut idx = 0;
-
-                while let Some(i) = name.get(idx..).and_then(|suffix| suffix.find('_')) {
-                    let bytes = unsafe { name[idx..].as_bytes_mut() };
-                    bytes[i] = b' ';
-                    idx = i + 1;
+    /// Remove the replay file and the partially rendered video of a job that won't finish
+    pub async fn remove_files(&self) {
+        for path in [self.path.clone(), self.id.video_path()] {
+            if let Err(err) = fs::remove_file(&path).await {
+                if err.kind() != ErrorKind::NotFound {
+                    let context = format!("failed to remove file of cancelled job at {path:?}");
+                    warn!("{:?}", Report::from(err).wrap_err(context));
                 }
-
-                Cow::Owned(name)
             }
         }
     }
+
+    pub fn requester(&self) -> Requester {
+        Requester {
+            user: self.user,
+            guild: self.guild,
+        }
+    }
+
+    pub fn map_length_or_default(&self) -> f32 {
+        self.map_length.unwrap_or(DEFAULT_MAP_LENGTH) as f32
+    }
+
+    /// Length of the rendered video in seconds
+    pub fn duration(&self) -> f32 {
+        let TimePoints { start, end } = self.time_points;
+        let length = self.map_length_or_default();
+
+        let end = if end == 0 {
+            length
+        } else {
+            length.min(end as f32)
+        };
+        let mods = GameMods::from_bits(self.replay.mods).unwrap_or(GameMods::NoMod);
+
+        let clock_rate = if mods.intersects(GameMods::DoubleTime | GameMods::NightCore) {
+            1.5
+        } else if mods.contains(GameMods::HalfTime) {
+            0.75
+        } else {
+            1.0
+        };
+
+        ((end - start as f32) / clock_rate).max(1.0)
+    }
+
+    /// Check whether the given input refers to this replay
+    /// either through its job id or its replay name.
+    pub fn matches(&self, name: &str) -> bool {
+        self.id.to_string() == name || self.name.eq_ignore_ascii_case(name)
+    }
+
+    pub fn replay_name(&self) -> &str {
+        &self.name
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -68,13 +118,83 @@ impl TimePoints {
     }
 }
 
+#[derive(Clone)]
+pub struct ActiveReplay {
+    pub data: ReplayData,
+    pub status: ReplayStatus,
+    pub cancel: Cancellation,
+    /// When the replay entered its current status
+    pub since: Instant,
+}
+
+impl ActiveReplay {
+    /// Tell the worker to stop unless the video is already being uploaded
+    pub fn try_cancel(&self) -> Cancelled {
+        if let ReplayStatus::Uploading(_) = self.status {
+            return Cancelled::Uploading(self.data.clone());
+        }
+
+        self.cancel.cancel();
+
+        Cancelled::Active(self.data.clone())
+    }
+}
+
+/// Signals a worker to stop processing its current replay
+#[derive(Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once [`Cancellation::cancel`] has been called
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking so a notification can't slip through
+            let notified = self.notify.notified();
+
+            if self.is_cancelled() {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum ReplayStatus {
     Waiting,
     Downloading,
     Rendering(u8),
     Encoding(u8),
-    Uploading,
+    Uploading(u8),
+}
+
+impl ReplayStatus {
+    pub fn progress(self) -> Option<u8> {
+        match self {
+            Self::Rendering(progress) | Self::Encoding(progress) | Self::Uploading(progress) => {
+                Some(progress)
+            }
+            Self::Waiting | Self::Downloading => None,
+        }
+    }
+
+    /// Whether both statuses belong to the same processing stage
+    pub(super) fn same_stage(self, other: Self) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
 }
 
 #[derive(Clone)]
@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use eyre::Report;
+use tokio::fs;
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+use crate::core::{BotConfig, Context};
+
+use super::TimePoints;
+
+/// Name of the danser settings that are used for the user's renders
+pub(super) fn settings_name(user: Id<UserMarker>) -> String {
+    if settings_path(&user.to_string()).exists() {
+        user.to_string()
+    } else {
+        "default".to_owned()
+    }
+}
+
+fn settings_path(name: &str) -> PathBuf {
+    let mut path = BotConfig::get().paths.danser().to_owned();
+    path.push(format!("settings/{name}.json"));
+
+    path
+}
+
+/// Hash of everything besides the replay itself that affects the rendered video
+pub async fn render_fingerprint(
+    user: Id<UserMarker>,
+    time_points: TimePoints,
+    pitch: Option<f64>,
+) -> String {
+    let path = settings_path(&settings_name(user));
+
+    let settings = match fs::read(&path).await {
+        Ok(settings) => settings,
+        Err(err) => {
+            let context = format!("failed to read danser settings at {path:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+
+            Vec::new()
+        }
+    };
+
+    let TimePoints { start, end } = time_points;
+
+    let mut hasher = md5::Context::new();
+    hasher.consume(&settings);
+    hasher.consume(format!("|{start}|{end}|{pitch:?}"));
+
+    format!("{:x}", hasher.compute())
+}
+
+/// Link to a previous render of the replay with the same
+/// fingerprint, as long as the video can still be watched.
+///
+/// Videos that were posted as attachment can only be watched by members of the
+/// guild they were posted in so they're only offered within that same guild.
+pub async fn existing_render(
+    ctx: &Context,
+    replay_hash: &str,
+    fingerprint: &str,
+    guild: Option<Id<GuildMarker>>,
+) -> Option<String> {
+    let render = match ctx.psql().render(replay_hash, fingerprint).await {
+        Ok(Some(render)) => render,
+        Ok(None) => return None,
+        Err(err) => {
+            warn!("{err:?}");
+
+            return None;
+        }
+    };
+
+    if render.channel_id.is_some() {
+        let same_guild = matches!(
+            (render.guild_id, guild),
+            (Some(render_guild), Some(guild)) if render_guild == guild.get() as i64
+        );
+
+        if !same_guild {
+            return None;
+        }
+    }
+
+    let is_valid = match (render.channel_id, render.message_id) {
+        (Some(channel), Some(message)) => ctx
+            .http
+            .message(Id::new(channel as u64), Id::new(message as u64))
+            .exec()
+            .await
+            .is_ok(),
+        _ => ctx.client().is_link_valid(&render.link).await,
+    };
+
+    if is_valid {
+        return Some(render.link);
+    }
+
+    // The video was deleted, a fresh render is needed
+    if let Err(err) = ctx.psql().remove_render(replay_hash, fingerprint).await {
+        warn!("{err:?}");
+    }
+
+    None
+}
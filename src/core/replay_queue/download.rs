@@ -0,0 +1,304 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use eyre::{Context as _, ContextCompat, Report, Result};
+use tokio::task;
+use zip::ZipArchive;
+
+use crate::{
+    core::{
+        mapset_cache::{mapset_path, MapsetLease},
+        BotConfig, Context,
+    },
+    custom_client::{MapsetDownloadError, MapsetMirror},
+};
+
+use super::{
+    process::{find_map_file, find_map_file_in, MapFile},
+    RenderError,
+};
+
+/// Maximum amount of files and folders in a mapset archive
+const MAX_ENTRIES: usize = 1000;
+
+/// Maximum amount of bytes a mapset archive may extract to
+const MAX_UNCOMPRESSED_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Distinguishes archives of concurrent downloads
+static DOWNLOAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Why a mirror could not provide the mapset, ordered by how far the download got
+#[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+enum MirrorFailure {
+    Download,
+    Archive,
+    WrongMapset,
+}
+
+/// Download the mapset into the songs folder and return the replay's map file.
+///
+/// Mirrors are tried in order until one provides a mapset that contains the map.
+/// The mapset is extracted into a folder of its own and only replaces the mapset's
+/// folder in the songs folder once it's complete and no other render uses it.
+/// The caller must hold the mapset's download lock.
+pub async fn download_mapset(
+    ctx: &Context,
+    lease: &MapsetLease<'_>,
+    mapset_id: u32,
+    map_hash: &str,
+) -> Result<MapFile, RenderError> {
+    let config = BotConfig::get();
+    let download_id = DOWNLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let mut archive_path = config.paths.downloads();
+    archive_path.push(format!("{mapset_id}_{download_id}.osz"));
+
+    // Stale folders in the work folder are removed by the janitor if the bot stops midway
+    let mut extract_dir = config.paths.work();
+    extract_dir.push(format!("mapset_{mapset_id}_{download_id}"));
+
+    // Slow downloads and extractions must not be mistaken as stale
+    let _protected = ctx
+        .mapsets
+        .protect_download(vec![archive_path.clone(), extract_dir.clone()]);
+
+    let mut errors = Vec::new();
+    let mut furthest = MirrorFailure::Download;
+
+    for mirror in ctx.client().mirror_order() {
+        let res = download_from_mirror(
+            ctx,
+            mirror,
+            mapset_id,
+            map_hash,
+            &archive_path,
+            &extract_dir,
+        )
+        .await;
+        mirror.record(res.is_ok());
+
+        match res {
+            Ok(_) => {
+                debug!("Downloaded mapset {mapset_id} from {}", mirror.name());
+                remove_archive(&archive_path).await;
+
+                return replace_mapset(lease, mapset_id, map_hash, &extract_dir).await;
+            }
+            Err((failure, err)) => {
+                furthest = furthest.max(failure);
+                errors.push((mirror.name().to_owned(), err));
+
+                // Don't leave parts of a bad mapset behind for the next mirror
+                if failure > MirrorFailure::Download {
+                    remove_dir(&extract_dir).await;
+                }
+            }
+        }
+    }
+
+    remove_archive(&archive_path).await;
+
+    let err = Report::from(MapsetDownloadError { mapset_id, errors });
+
+    let err = match furthest {
+        MirrorFailure::Download => RenderError::MirrorsDown(err),
+        MirrorFailure::Archive => RenderError::MapsetArchive(err),
+        MirrorFailure::WrongMapset => RenderError::WrongMapset(err),
+    };
+
+    Err(err)
+}
+
+async fn download_from_mirror(
+    ctx: &Context,
+    mirror: &MapsetMirror,
+    mapset_id: u32,
+    map_hash: &str,
+    archive_path: &Path,
+    extract_dir: &Path,
+) -> Result<(), (MirrorFailure, Report)> {
+    let max_size = BotConfig::get().mapsets.max_download_size;
+
+    ctx.client()
+        .download_mapset(mirror, mapset_id, archive_path, max_size)
+        .await
+        .map_err(|err| (MirrorFailure::Download, err))?;
+
+    let archive = archive_path.to_owned();
+    let out_dir = extract_dir.to_owned();
+
+    task::spawn_blocking(move || extract_mapset(&archive, &out_dir))
+        .await
+        .context("failed to join mapset extraction")
+        .and_then(|res| res)
+        .map_err(|err| (MirrorFailure::Archive, err))?;
+
+    match find_map_file_in(extract_dir, map_hash).await {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            let err = err.wrap_err(format!(
+                "mirror returned a wrong or outdated version of mapset {mapset_id}"
+            ));
+
+            Err((MirrorFailure::WrongMapset, err))
+        }
+    }
+}
+
+/// Swap the freshly extracted mapset into the songs folder
+async fn replace_mapset(
+    lease: &MapsetLease<'_>,
+    mapset_id: u32,
+    map_hash: &str,
+    extract_dir: &Path,
+) -> Result<MapFile, RenderError> {
+    // Other workers might still render from an older version of the mapset
+    lease.wait_exclusive().await;
+
+    if remove_mapset(mapset_id).await {
+        info!("Replaced outdated mapset {mapset_id}");
+    }
+
+    let mapset_dir = mapset_path(mapset_id);
+
+    if let Err(err) = tokio::fs::rename(extract_dir, &mapset_dir).await {
+        remove_dir(extract_dir).await;
+        let context = format!("failed to move mapset from {extract_dir:?} to {mapset_dir:?}");
+
+        return Err(RenderError::MapsetArchive(
+            Report::from(err).wrap_err(context),
+        ));
+    }
+
+    find_map_file(mapset_id, map_hash)
+        .await
+        .map_err(RenderError::WrongMapset)
+}
+
+/// Remove the mapset's folder if it exists.
+///
+/// Returns whether there was a folder to remove.
+async fn remove_mapset(mapset_id: u32) -> bool {
+    let mapset_dir = mapset_path(mapset_id);
+
+    match tokio::fs::remove_dir_all(&mapset_dir).await {
+        Ok(_) => true,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+        Err(err) => {
+            let context = format!("failed to remove mapset at {mapset_dir:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+
+            false
+        }
+    }
+}
+
+async fn remove_dir(dir: &Path) {
+    if let Err(err) = tokio::fs::remove_dir_all(dir).await {
+        if err.kind() != io::ErrorKind::NotFound {
+            let context = format!("failed to remove folder at {dir:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+        }
+    }
+}
+
+async fn remove_archive(archive_path: &Path) {
+    if let Err(err) = tokio::fs::remove_file(archive_path).await {
+        if err.kind() != io::ErrorKind::NotFound {
+            let context = format!("failed to remove mapset archive at {archive_path:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+        }
+    }
+}
+
+/// Extract the archive after checking that it stays within
+/// the limits and that none of its entries escape `out_dir`.
+///
+/// On failure, everything that was extracted already is removed again
+/// so `out_dir` must not be shared with anything else.
+fn extract_mapset(archive_path: &Path, out_dir: &Path) -> Result<()> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("failed to open mapset archive at {archive_path:?}"))?;
+
+    let mut archive =
+        ZipArchive::new(BufReader::new(file)).context("failed to read zip archive")?;
+
+    if archive.len() > MAX_ENTRIES {
+        bail!(
+            "archive contains {} entries, the limit is {MAX_ENTRIES}",
+            archive.len()
+        );
+    }
+
+    let mut paths = Vec::with_capacity(archive.len());
+    let mut total_size = 0;
+
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .with_context(|| format!("failed to read entry #{i} of zip archive"))?;
+
+        let path = entry
+            .enclosed_name()
+            .with_context(|| format!("entry `{}` escapes the mapset folder", entry.name()))?;
+
+        paths.push(out_dir.join(path));
+        total_size += entry.size();
+
+        if total_size > MAX_UNCOMPRESSED_SIZE {
+            bail!("archive extracts to more than {MAX_UNCOMPRESSED_SIZE} bytes");
+        }
+    }
+
+    let res = extract_entries(&mut archive, paths);
+
+    if res.is_err() {
+        let _ = fs::remove_dir_all(out_dir);
+    }
+
+    res
+}
+
+fn extract_entries<R>(archive: &mut ZipArchive<R>, paths: Vec<PathBuf>) -> Result<()>
+where
+    R: Read + io::Seek,
+{
+    // The sizes in the archive's headers could be lying so keep track of the actual amount
+    let mut remaining = MAX_UNCOMPRESSED_SIZE;
+
+    for (i, path) in paths.into_iter().enumerate() {
+        let mut entry = archive
+            .by_index(i)
+            .with_context(|| format!("failed to read entry #{i} of zip archive"))?;
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path)
+                .with_context(|| format!("failed to create folder at {path:?}"))?;
+
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create folder at {parent:?}"))?;
+        }
+
+        let mut file =
+            File::create(&path).with_context(|| format!("failed to create file at {path:?}"))?;
+
+        let written = io::copy(&mut (&mut entry).take(remaining + 1), &mut file)
+            .with_context(|| format!("failed to extract file to {path:?}"))?;
+
+        if written > remaining {
+            bail!("archive extracts to more than {MAX_UNCOMPRESSED_SIZE} bytes");
+        }
+
+        remaining -= written;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use eyre::Report;
+
+use super::danser::DanserError;
+
+/// Everything that can go wrong while processing a replay
+#[derive(Debug)]
+pub enum RenderError {
+    Cancelled,
+    DanserFailed(DanserError),
+    InvalidLink(String),
+    WrongMapset(Report),
+    MapNotFound(Report),
+    MapsetArchive(Report),
+    MirrorsDown(Report),
+    MissingMapHash,
+    MissingMapset,
+    MissingReplayHash,
+    Title(Report),
+    UploadPending(Report),
+    UploadRejected(String),
+}
+
+impl RenderError {
+    /// Explanation for the user whose replay failed
+    pub fn user_message(&self) -> Cow<'static, str> {
+        let msg: Cow<'static, str> = match self {
+            Self::Cancelled => "The replay was cancelled".into(),
+            Self::DanserFailed(err) => err.user_message().into(),
+            Self::InvalidLink(_) => "The video host responded with an invalid link".into(),
+            Self::WrongMapset(_) => {
+                "The mirrors only provided a different or outdated version of the mapset".into()
+            }
+            Self::MapNotFound(_) => "Failed to retrieve map. Maybe it's not submitted?".into(),
+            Self::MapsetArchive(_) => "The downloaded mapset could not be extracted".into(),
+            Self::MirrorsDown(_) => "Failed to download map, mirrors are likely down".into(),
+            Self::MissingMapHash => "Missing the beatmap hash in the replay file".into(),
+            Self::MissingMapset => {
+                "The mapset was not received when requesting the map from the osu!api".into()
+            }
+            Self::MissingReplayHash => "Could not get the replay hash".into(),
+            Self::Title(_) => "There was an error while trying to create the video title".into(),
+            Self::UploadPending(_) => {
+                "Failed to upload the video, it will be retried automatically. \
+                Use `/queue reupload` to retry right away."
+                    .into()
+            }
+            Self::UploadRejected(text) => format!("Failed to upload: `{text}`").into(),
+        };
+
+        if self.is_retryable() {
+            format!("{msg}\nThis is likely temporary, feel free to try again later.").into()
+        } else {
+            msg
+        }
+    }
+
+    /// Whether the error is likely to go away if the replay is processed again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::DanserFailed(err) => matches!(
+                err,
+                DanserError::Spawn(_) | DanserError::Timeout(_) | DanserError::Wait(_)
+            ),
+            Self::MirrorsDown(_) => true,
+            Self::Cancelled
+            | Self::InvalidLink(_)
+            | Self::WrongMapset(_)
+            | Self::MapNotFound(_)
+            | Self::MapsetArchive(_)
+            | Self::MissingMapHash
+            | Self::MissingMapset
+            | Self::MissingReplayHash
+            | Self::Title(_)
+            | Self::UploadPending(_)
+            | Self::UploadRejected(_) => false,
+        }
+    }
+
+    /// Short name of the error, used to group failures in stats
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Cancelled => "Cancelled",
+            Self::DanserFailed(_) => "Danser failed",
+            Self::InvalidLink(_) => "Invalid link",
+            Self::WrongMapset(_) => "Wrong mapset",
+            Self::MapNotFound(_) => "Map not found",
+            Self::MapsetArchive(_) => "Mapset archive",
+            Self::MirrorsDown(_) => "Mirrors down",
+            Self::MissingMapHash => "Missing map hash",
+            Self::MissingMapset => "Missing mapset",
+            Self::MissingReplayHash => "Missing replay hash",
+            Self::Title(_) => "Video title",
+            Self::UploadPending(_) => "Upload pending",
+            Self::UploadRejected(_) => "Upload rejected",
+        }
+    }
+
+    /// Turn the error into a report to log, including all its causes
+    pub fn into_report(self) -> Report {
+        let context = self.to_string();
+
+        match self {
+            Self::DanserFailed(err) => Report::new(err).wrap_err(context),
+            Self::WrongMapset(err)
+            | Self::MapNotFound(err)
+            | Self::MapsetArchive(err)
+            | Self::MirrorsDown(err)
+            | Self::Title(err)
+            | Self::UploadPending(err) => err.wrap_err(context),
+            Self::Cancelled
+            | Self::InvalidLink(_)
+            | Self::MissingMapHash
+            | Self::MissingMapset
+            | Self::MissingReplayHash
+            | Self::UploadRejected(_) => Report::msg(context),
+        }
+    }
+}
+
+impl Display for RenderError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Cancelled => f.write_str("replay was cancelled"),
+            Self::DanserFailed(_) => f.write_str("danser failed"),
+            Self::InvalidLink(link) => write!(f, "upload responded with invalid link {link:?}"),
+            Self::WrongMapset(_) => f.write_str("mirrors returned a wrong or outdated mapset"),
+            Self::MapNotFound(_) => f.write_str("failed to request map"),
+            Self::MapsetArchive(_) => f.write_str("failed to extract mapset archive"),
+            Self::MirrorsDown(_) => f.write_str("failed to download mapset"),
+            Self::MissingMapHash => f.write_str("missing hash in replay"),
+            Self::MissingMapset => f.write_str("map without mapset"),
+            Self::MissingReplayHash => f.write_str("replay without replay hash"),
+            Self::Title(_) => f.write_str("failed to create title"),
+            Self::UploadPending(_) => f.write_str("failed to upload file, retrying later"),
+            Self::UploadRejected(text) => write!(f, "failed to upload: `{text}`"),
+        }
+    }
+}
+
+impl From<DanserError> for RenderError {
+    #[inline]
+    fn from(err: DanserError) -> Self {
+        match err {
+            DanserError::Cancelled => Self::Cancelled,
+            err => Self::DanserFailed(err),
+        }
+    }
+}
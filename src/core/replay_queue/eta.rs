@@ -0,0 +1,155 @@
+use std::{sync::Mutex, time::Duration};
+
+use super::{ActiveReplay, ReplayData, ReplayStatus};
+
+/// Weight of a new sample in the moving average of a stage
+const SAMPLE_WEIGHT: f32 = 0.2;
+
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+enum Stage {
+    Download,
+    Render,
+    Encode,
+    Upload,
+}
+
+const STAGES: [Stage; 4] = [Stage::Download, Stage::Render, Stage::Encode, Stage::Upload];
+
+impl Stage {
+    fn from_status(status: ReplayStatus) -> Option<Self> {
+        match status {
+            ReplayStatus::Waiting => None,
+            ReplayStatus::Downloading => Some(Self::Download),
+            ReplayStatus::Rendering(_) => Some(Self::Render),
+            ReplayStatus::Encoding(_) => Some(Self::Encode),
+            ReplayStatus::Uploading(_) => Some(Self::Upload),
+        }
+    }
+
+    /// Seconds the duration of this stage scales with.
+    ///
+    /// Downloading depends on the mapset and thus the map length,
+    /// everything else on how long the resulting video will be.
+    ///
+    /// At least one second so that maps without length can't break the ratios.
+    fn reference(self, data: &ReplayData) -> f32 {
+        let reference = match self {
+            Self::Download => data.map_length_or_default(),
+            Self::Render | Self::Encode | Self::Upload => data.duration(),
+        };
+
+        reference.max(1.0)
+    }
+}
+
+/// Tracks how many seconds each stage takes per reference second
+/// of a replay to estimate how long replays will take.
+pub struct StageTimings {
+    ratios: Mutex<[f32; 4]>,
+}
+
+impl Default for StageTimings {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            ratios: Mutex::new([0.05, 0.6, 0.2, 0.05]),
+        }
+    }
+}
+
+impl StageTimings {
+    /// Add a sample of how long a replay spent in the given status
+    pub fn record(&self, status: ReplayStatus, elapsed: Duration, data: &ReplayData) {
+        let stage = match Stage::from_status(status) {
+            Some(stage) => stage,
+            None => return,
+        };
+
+        let sample = elapsed.as_secs_f32() / stage.reference(data);
+
+        if !sample.is_finite() {
+            return;
+        }
+
+        let ratio = &mut self.ratios.lock().unwrap()[stage as usize];
+        *ratio = *ratio * (1.0 - SAMPLE_WEIGHT) + sample * SAMPLE_WEIGHT;
+    }
+
+    /// Estimate how long until each of the replays is done.
+    ///
+    /// Waiting replays are assigned to whichever worker is expected to be free first.
+    pub fn estimate<'d, I>(&self, active: &[ActiveReplay], waiting: I, workers: usize) -> Estimates
+    where
+        I: IntoIterator<Item = &'d ReplayData>,
+    {
+        let ratios = *self.ratios.lock().unwrap();
+        let stage_secs =
+            |stage: Stage, data: &ReplayData| ratios[stage as usize] * stage.reference(data);
+
+        let active_secs: Vec<f32> = active
+            .iter()
+            .map(|active| {
+                let curr = Stage::from_status(active.status);
+
+                STAGES
+                    .iter()
+                    .map(|&stage| match curr {
+                        Some(curr) if stage < curr => 0.0,
+                        Some(curr) if stage == curr => {
+                            let elapsed = active.since.elapsed().as_secs_f32();
+
+                            match active.status.progress() {
+                                Some(progress @ 5..=100) => {
+                                    elapsed * (100 - progress) as f32 / progress as f32
+                                }
+                                _ => (stage_secs(stage, &active.data) - elapsed).max(0.0),
+                            }
+                        }
+                        _ => stage_secs(stage, &active.data),
+                    })
+                    .sum()
+            })
+            .collect();
+
+        let mut free_at = active_secs.clone();
+        free_at.resize(workers.max(active_secs.len()), 0.0);
+
+        let waiting = waiting
+            .into_iter()
+            .map(|data| {
+                let (idx, start) = free_at
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .unwrap_or((0, 0.0));
+
+                let done = start
+                    + STAGES
+                        .iter()
+                        .map(|&stage| stage_secs(stage, data))
+                        .sum::<f32>();
+
+                if let Some(free) = free_at.get_mut(idx) {
+                    *free = done;
+                }
+
+                Duration::from_secs_f32(done)
+            })
+            .collect();
+
+        Estimates {
+            active: active_secs
+                .into_iter()
+                .map(Duration::from_secs_f32)
+                .collect(),
+            waiting,
+        }
+    }
+}
+
+/// Estimated durations until replays are done, in the same order as the queue
+pub struct Estimates {
+    pub active: Vec<Duration>,
+    pub waiting: Vec<Duration>,
+}
@@ -0,0 +1,95 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::{core::BotConfig, util::CowUtils};
+
+/// Maximum amount of chars in a replay's display name, the limit of autocomplete choices
+const MAX_NAME_LEN: usize = 100;
+
+/// Identifies a render job, all of its files are named after it
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct JobId(u64);
+
+impl JobId {
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+
+    /// Where the job's replay file is stored
+    pub fn replay_path(self) -> PathBuf {
+        let mut path = BotConfig::get().paths.downloads();
+        path.push(format!("{self}.osr"));
+
+        path
+    }
+
+    /// Where danser writes the job's video to
+    pub fn video_path(self) -> PathBuf {
+        let mut path = BotConfig::get().paths.replays();
+        path.push(format!("{self}.mp4"));
+
+        path
+    }
+
+    /// Working directory that danser runs in for the job
+    pub fn work_dir(self) -> PathBuf {
+        let mut path = BotConfig::get().paths.work();
+        path.push(self.to_string());
+
+        path
+    }
+
+    /// Where danser's output of the job is stored
+    pub fn log_path(self) -> PathBuf {
+        let mut path = BotConfig::get().paths.logs();
+        path.push(format!("{self}.log"));
+
+        path
+    }
+}
+
+impl Display for JobId {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for JobId {
+    type Err = ();
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 {
+            return Err(());
+        }
+
+        u64::from_str_radix(s, 16).map(Self).map_err(|_| ())
+    }
+}
+
+/// Human-friendly name of a replay based on the name of its uploaded file,
+/// e.g. `mezo_-_Artist_-_Title_[Diff]_(2022-11-30)_Osu.osr`
+pub fn replay_name_from_file(file_name: &str) -> String {
+    let extension = file_name.rfind(".osr").unwrap_or(file_name.len());
+    let suffix = file_name[..extension].rfind("_Osu").unwrap_or(extension);
+
+    sanitize_replay_name(&file_name[..suffix].cow_replace('_', " "))
+}
+
+/// Strip control characters and limit the length so the name can be displayed anywhere
+pub fn sanitize_replay_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LEN)
+        .collect();
+
+    match name.trim() {
+        "" => "<unnamed replay>".to_owned(),
+        trimmed => trimmed.to_owned(),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::database::Database;
+
+use super::{Cancelled, ReplayData, ReplayQueue};
+
+impl ReplayQueue {
+    pub fn is_paused(&self) -> bool {
+        *self.paused_rx.borrow()
+    }
+
+    /// Prevent workers from starting new replays.
+    ///
+    /// Returns `false` if the queue was already paused.
+    pub fn pause(&self) -> bool {
+        self.set_paused(true)
+    }
+
+    /// Let workers start new replays again.
+    ///
+    /// Returns `false` if the queue was not paused.
+    pub fn resume(&self) -> bool {
+        self.set_paused(false)
+    }
+
+    fn set_paused(&self, paused: bool) -> bool {
+        if self.is_paused() == paused {
+            return false;
+        }
+
+        let _ = self.paused_tx.send(paused);
+
+        true
+    }
+
+    pub(super) async fn wait_until_resumed(&self) {
+        let mut rx = self.paused_rx.clone();
+
+        while *rx.borrow() {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Remove the replay at the given 1-based position, counting
+    /// the replays in progress first and then the waiting ones.
+    ///
+    /// Replays in progress are told to stop while waiting replays are removed right away.
+    /// Replays that are already being uploaded are left alone.
+    /// The position is resolved while holding the queue lock so that workers can't pick
+    /// up a replay in the meantime and shift the position onto another replay.
+    pub async fn remove_at(&self, psql: &Database, position: usize) -> Cancelled {
+        let idx = match position.checked_sub(1) {
+            Some(idx) => idx,
+            None => return Cancelled::NotFound,
+        };
+
+        let mut queue_guard = self.queue.lock().await;
+        let mut active = 0;
+
+        // Check the status while holding the worker's lock so the upload can't start in between
+        for worker in self.workers.iter() {
+            if let Some(ref replay) = *worker.lock().await {
+                if active == idx {
+                    return replay.try_cancel();
+                }
+
+                active += 1;
+            }
+        }
+
+        let data = match queue_guard.remove(idx - active) {
+            Some(data) => data,
+            None => return Cancelled::NotFound,
+        };
+
+        drop(queue_guard);
+
+        if let Err(err) = psql.remove_queued_replay(data.id).await {
+            warn!("{err:?}");
+        }
+
+        data.remove_files().await;
+
+        Cancelled::Waiting(data)
+    }
+
+    /// Move the waiting replay at the 1-based position `from` to position `to`.
+    ///
+    /// Positions count the replays in progress first, those can't be moved.
+    pub async fn move_entry(&self, from: usize, to: usize) -> Option<ReplayData> {
+        let mut queue_guard = self.queue.lock().await;
+        let active = self.active().await.len();
+
+        let from = from.checked_sub(active + 1)?;
+        let to = to.checked_sub(active + 1)?;
+
+        if to >= queue_guard.len() {
+            return None;
+        }
+
+        let data = queue_guard.remove(from)?;
+        queue_guard.insert(to, data.clone());
+
+        Some(data)
+    }
+
+    /// Remove all waiting replays
+    pub async fn clear(&self, psql: &Database) -> Vec<ReplayData> {
+        let removed: Vec<_> = self.queue.lock().await.drain(..).collect();
+
+        if !removed.is_empty() {
+            if let Err(err) = psql.remove_queued_replays(&removed).await {
+                warn!("{err:?}");
+            }
+        }
+
+        for data in removed.iter() {
+            data.remove_files().await;
+        }
+
+        removed
+    }
+}
@@ -1,73 +1,278 @@
-use std::collections::VecDeque;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
 use tokio::sync::{
     mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    watch::{self, Receiver as WatchReceiver, Sender as WatchSender},
     Mutex,
 };
+use twilight_model::id::{marker::UserMarker, Id};
+
+use crate::database::Database;
 
-pub use self::data::*;
+pub use self::{
+    data::*,
+    dedup::{existing_render, render_fingerprint},
+    error::RenderError,
+    eta::Estimates,
+    job::{replay_name_from_file, sanitize_replay_name, JobId},
+    pending::{PendingUpload, PendingUploads},
+    progress::{ProgressMessage, ProgressTracker},
+    schedule::Requester,
+};
 
+use self::{eta::StageTimings, schedule::fair_position};
+
+mod danser;
 mod data;
+mod dedup;
+mod download;
+mod error;
+mod eta;
+mod job;
+mod manage;
+mod pending;
 mod process;
+mod progress;
+mod restore;
+mod schedule;
+
+pub enum Cancelled {
+    /// The replay was removed from the queue
+    Waiting(ReplayData),
+    /// The replay's worker was told to stop
+    Active(ReplayData),
+    /// The replay is already being uploaded and can't be stopped anymore
+    Uploading(ReplayData),
+    NotFound,
+}
 
 pub struct ReplayQueue {
+    /// Replays that are waiting for a worker
     pub queue: Mutex<VecDeque<ReplayData>>,
-    pub status: Mutex<ReplayStatus>,
+    /// The replay each worker is currently processing
+    pub workers: Box<[Mutex<Option<ActiveReplay>>]>,
+    /// Rendered videos whose upload is retried later
+    pub pending_uploads: PendingUploads,
     tx: UnboundedSender<()>,
     rx: Mutex<UnboundedReceiver<()>>,
+    paused_tx: WatchSender<bool>,
+    paused_rx: WatchReceiver<bool>,
+    timings: StageTimings,
 }
 
 impl ReplayQueue {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(workers: usize) -> Self {
+        let (tx, rx) = unbounded_channel();
+        let (paused_tx, paused_rx) = watch::channel(false);
+
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+            workers: (0..workers).map(|_| Mutex::new(None)).collect(),
+            pending_uploads: PendingUploads::default(),
+            tx,
+            rx: Mutex::new(rx),
+            paused_tx,
+            paused_rx,
+            timings: StageTimings::default(),
+        }
     }
 
-    pub async fn push(&self, data: ReplayData) {
-        self.queue.lock().await.push_back(data);
+    /// Add a replay to the queue and return how long until it's estimated to be done.
+    ///
+    /// The estimate is taken while still holding the queue lock so that the
+    /// replay can't be picked up or shifted around before it's estimated.
+    pub async fn push(&self, psql: &Database, data: ReplayData) -> Option<Duration> {
+        // Failing to persist the entry only means it won't survive a restart
+        if let Err(err) = psql.insert_queued_replay(&data).await {
+            warn!("{err:?}");
+        }
+
+        let mut queue_guard = self.queue.lock().await;
+        let active = self.active().await;
+
+        let idx = fair_position(
+            active.iter().map(|active| active.data.requester()),
+            queue_guard.iter().map(ReplayData::requester),
+            data.requester(),
+        );
+
+        queue_guard.insert(idx, data);
         let _ = self.tx.send(());
-    }
 
-    pub async fn pop(&self) -> ReplayData {
-        self.queue.lock().await.pop_front().unwrap()
+        let estimates = self.estimate_with(&active, &queue_guard);
+
+        estimates.waiting.get(idx).copied()
     }
 
-    pub async fn peek(&self) -> ReplayData {
-        trace!("Locking channel receiver...");
-        let mut guard = self.rx.lock().await;
-        trace!("Locked receiver, awaiting entry...");
-        let _ = guard.recv().await;
-        trace!("Received entry, locking queue...");
+    /// How many replays of the user are waiting or being processed
+    pub async fn queued_by(&self, user: Id<UserMarker>) -> usize {
         let queue_guard = self.queue.lock().await;
-        trace!("Locked queue");
 
-        queue_guard.front().unwrap().to_owned()
+        let waiting = queue_guard.iter().filter(|data| data.user == user).count();
+
+        let active = self
+            .active()
+            .await
+            .iter()
+            .filter(|active| active.data.user == user)
+            .count();
+
+        waiting + active
     }
 
-    pub async fn set_status(&self, status: ReplayStatus) {
-        trace!("Updating progress status to {status:?}...");
-        *self.status.lock().await = status;
-        trace!("Updated progress status");
+    /// Wait for the next replay and assign it to the given worker
+    pub async fn next(&self, worker: usize) -> ActiveReplay {
+        loop {
+            self.wait_until_resumed().await;
+
+            trace!("[Worker {worker}] Locking channel receiver...");
+            let mut guard = self.rx.lock().await;
+            trace!("[Worker {worker}] Locked receiver, awaiting entry...");
+            let _ = guard.recv().await;
+            drop(guard);
+
+            // The queue might have been paused while waiting for an entry
+            if self.is_paused() {
+                let _ = self.tx.send(());
+                continue;
+            }
+
+            trace!("[Worker {worker}] Received entry, locking queue...");
+            let mut queue_guard = self.queue.lock().await;
+            trace!("[Worker {worker}] Locked queue");
+
+            if let Some(data) = queue_guard.pop_front() {
+                let active = ActiveReplay {
+                    data,
+                    status: ReplayStatus::Waiting,
+                    cancel: Cancellation::default(),
+                    since: Instant::now(),
+                };
+
+                *self.workers[worker].lock().await = Some(active.clone());
+
+                return active;
+            }
+        }
     }
 
-    pub async fn reset_peek(&self) {
-        trace!("Resetting peek...");
-        *self.status.lock().await = ReplayStatus::Waiting;
-        trace!("Peek reset, popping queue...");
-        let _ = self.pop().await;
-        trace!("Popped queue");
+    pub async fn set_status(&self, worker: usize, status: ReplayStatus) {
+        trace!("[Worker {worker}] Updating progress status to {status:?}...");
+
+        if let Some(ref mut active) = *self.workers[worker].lock().await {
+            self.update_status(active, status);
+        }
+
+        trace!("[Worker {worker}] Updated progress status");
     }
-}
 
-impl Default for ReplayQueue {
-    #[inline]
-    fn default() -> Self {
-        let (tx, rx) = unbounded_channel();
+    /// Switch the worker's replay to uploading unless it was cancelled already.
+    ///
+    /// Once the upload started, the replay can't be cancelled anymore.
+    pub async fn start_upload(&self, worker: usize) -> bool {
+        match *self.workers[worker].lock().await {
+            Some(ref mut active) if !active.cancel.is_cancelled() => {
+                self.update_status(active, ReplayStatus::Uploading(0));
 
-        Self {
-            queue: Mutex::new(VecDeque::new()),
-            tx,
-            rx: Mutex::new(rx),
-            status: Mutex::new(ReplayStatus::Waiting),
+                true
+            }
+            _ => false,
         }
     }
+
+    fn update_status(&self, active: &mut ActiveReplay, status: ReplayStatus) {
+        if !active.status.same_stage(status) {
+            let elapsed = active.since.elapsed();
+            self.timings.record(active.status, elapsed, &active.data);
+            active.since = Instant::now();
+        }
+
+        active.status = status;
+    }
+
+    /// Release the worker's current replay
+    pub async fn finish(&self, psql: &Database, worker: usize) {
+        trace!("[Worker {worker}] Finishing replay...");
+        let active = self.workers[worker].lock().await.take();
+        trace!("[Worker {worker}] Finished replay");
+
+        if let Some(ActiveReplay { data, .. }) = active {
+            if let Err(err) = psql.remove_queued_replay(data.id).await {
+                warn!("{err:?}");
+            }
+        }
+    }
+
+    /// Release the worker's replay after it was processed successfully.
+    ///
+    /// Unlike [`ReplayQueue::finish`], the time of its last stage is
+    /// taken into account for future estimates.
+    pub async fn complete(&self, psql: &Database, worker: usize) {
+        if let Some(ref active) = *self.workers[worker].lock().await {
+            let elapsed = active.since.elapsed();
+            self.timings.record(active.status, elapsed, &active.data);
+        }
+
+        self.finish(psql, worker).await;
+    }
+
+    /// Cancel a replay of the user.
+    ///
+    /// Waiting replays are removed from the queue right away while
+    /// replays that are being processed will be stopped by their worker.
+    /// Replays that are already being uploaded are left alone.
+    pub async fn cancel(&self, psql: &Database, user: Id<UserMarker>, name: &str) -> Cancelled {
+        let mut queue_guard = self.queue.lock().await;
+
+        let idx_opt = queue_guard
+            .iter()
+            .position(|data| data.user == user && data.matches(name));
+
+        if let Some(data) = idx_opt.and_then(|idx| queue_guard.remove(idx)) {
+            drop(queue_guard);
+
+            if let Err(err) = psql.remove_queued_replay(data.id).await {
+                warn!("{err:?}");
+            }
+
+            data.remove_files().await;
+
+            return Cancelled::Waiting(data);
+        }
+
+        for worker in self.workers.iter() {
+            if let Some(ref active) = *worker.lock().await {
+                if active.data.user == user && active.data.matches(name) {
+                    return active.try_cancel();
+                }
+            }
+        }
+
+        Cancelled::NotFound
+    }
+
+    /// Snapshot of all replays that are currently being processed
+    pub async fn active(&self) -> Vec<ActiveReplay> {
+        let mut active = Vec::with_capacity(self.workers.len());
+
+        for worker in self.workers.iter() {
+            if let Some(ref replay) = *worker.lock().await {
+                active.push(replay.clone());
+            }
+        }
+
+        active
+    }
+
+    /// Estimate how long until each of the given active and waiting replays is done
+    pub fn estimate_with(
+        &self,
+        active: &[ActiveReplay],
+        queue: &VecDeque<ReplayData>,
+    ) -> Estimates {
+        self.timings.estimate(active, queue, self.workers.len())
+    }
 }
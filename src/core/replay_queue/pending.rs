@@ -0,0 +1,400 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eyre::{Context as _, Report, Result};
+use tokio::{
+    fs,
+    sync::{Mutex, Notify},
+    time,
+};
+use twilight_model::id::{
+    marker::{ChannelMarker, UserMarker},
+    Id,
+};
+
+use crate::{
+    core::Context,
+    custom_client::VideoUpload,
+    database::{DBPendingUpload, DBRender, Database},
+    util::{builder::MessageBuilder, ChannelExt},
+};
+
+/// Delay before the first retry, doubled for every further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Rejection reasons are cut off after this many chars so the notification fits into a message
+const REJECTION_LIMIT: usize = 500;
+
+/// Upper bound for the delay between two retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Failed attempts after which an upload is given up on
+const MAX_ATTEMPTS: u32 = 8;
+
+/// A rendered video whose upload failed and that will be retried later
+#[derive(Clone)]
+pub struct PendingUpload {
+    pub video_path: PathBuf,
+    pub user: Id<UserMarker>,
+    pub output_channel: Id<ChannelMarker>,
+    pub title: String,
+    pub mapset_id: u32,
+    pub replay_hash: String,
+    /// Identifies the render's settings, see [`render_fingerprint`](super::render_fingerprint)
+    pub fingerprint: Option<String>,
+    /// How many upload attempts failed already
+    pub attempts: u32,
+    next_attempt: Instant,
+    /// Whether the upload is currently being retried
+    retrying: bool,
+}
+
+impl PendingUpload {
+    pub fn new(
+        video_path: PathBuf,
+        user: Id<UserMarker>,
+        output_channel: Id<ChannelMarker>,
+        title: String,
+        mapset_id: u32,
+        replay_hash: String,
+        fingerprint: String,
+    ) -> Self {
+        let mut upload = Self {
+            video_path,
+            user,
+            output_channel,
+            title,
+            mapset_id,
+            replay_hash,
+            fingerprint: Some(fingerprint),
+            attempts: 1,
+            next_attempt: Instant::now(),
+            retrying: false,
+        };
+
+        upload.schedule_retry();
+
+        upload
+    }
+
+    fn schedule_retry(&mut self) {
+        let delay = RETRY_BASE_DELAY
+            .saturating_mul(1 << (self.attempts - 1).min(16))
+            .min(RETRY_MAX_DELAY);
+
+        self.next_attempt = Instant::now() + delay;
+    }
+}
+
+/// Videos that were rendered but could not be uploaded yet
+#[derive(Default)]
+pub struct PendingUploads {
+    uploads: Mutex<Vec<PendingUpload>>,
+    notify: Notify,
+}
+
+impl PendingUploads {
+    /// Keep the video around and retry its upload later
+    pub async fn add(&self, psql: &Database, upload: PendingUpload) {
+        // Failing to persist the entry only means it won't survive a restart
+        if let Err(err) = psql.insert_pending_upload(&upload).await {
+            warn!("{err:?}");
+        }
+
+        self.uploads.lock().await.push(upload);
+        self.notify.notify_one();
+    }
+
+    /// Retry all pending uploads of the user right away.
+    ///
+    /// Returns the titles of the affected videos.
+    pub async fn retry_now(&self, user: Id<UserMarker>) -> Vec<String> {
+        let now = Instant::now();
+
+        let titles: Vec<_> = self
+            .uploads
+            .lock()
+            .await
+            .iter_mut()
+            .filter(|upload| upload.user == user)
+            .map(|upload| {
+                upload.next_attempt = now;
+
+                upload.title.clone()
+            })
+            .collect();
+
+        if !titles.is_empty() {
+            self.notify.notify_one();
+        }
+
+        titles
+    }
+
+    /// Load the pending uploads that were stored before the last shutdown
+    pub async fn restore(&self, psql: &Database) -> Result<()> {
+        let entries = psql
+            .pending_uploads()
+            .await
+            .context("failed to get pending uploads")?;
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let total = entries.len();
+        let mut uploads = Vec::with_capacity(total);
+
+        for entry in entries {
+            let DBPendingUpload {
+                video_path,
+                user_id,
+                output_channel,
+                title,
+                mapset_id,
+                replay_hash,
+                settings_hash,
+                attempts,
+            } = entry;
+
+            let video_path = PathBuf::from(video_path);
+
+            if !video_path.exists() {
+                warn!("Video of pending upload at {video_path:?} no longer exists");
+
+                if let Err(err) = psql.remove_pending_upload(&video_path).await {
+                    warn!("{err:?}");
+                }
+
+                continue;
+            }
+
+            uploads.push(PendingUpload {
+                video_path,
+                user: Id::new(user_id as u64),
+                output_channel: Id::new(output_channel as u64),
+                title,
+                mapset_id: mapset_id as u32,
+                replay_hash,
+                fingerprint: settings_hash,
+                attempts: attempts as u32,
+                next_attempt: Instant::now(),
+                retrying: false,
+            });
+        }
+
+        info!("Restored {}/{total} pending uploads", uploads.len());
+
+        self.uploads.lock().await.extend(uploads);
+        self.notify.notify_one();
+
+        Ok(())
+    }
+
+    /// Paths of all videos that are waiting to be uploaded
+    pub async fn video_paths(&self) -> Vec<PathBuf> {
+        self.uploads
+            .lock()
+            .await
+            .iter()
+            .map(|upload| upload.video_path.clone())
+            .collect()
+    }
+
+    /// Wait until uploads are due and mark them as being retried
+    async fn next_due(&self) -> Vec<PendingUpload> {
+        loop {
+            let mut uploads = self.uploads.lock().await;
+            let now = Instant::now();
+
+            let due: Vec<_> = uploads
+                .iter_mut()
+                .filter(|upload| !upload.retrying && upload.next_attempt <= now)
+                .map(|upload| {
+                    upload.retrying = true;
+
+                    upload.clone()
+                })
+                .collect();
+
+            if !due.is_empty() {
+                return due;
+            }
+
+            let next_attempt = uploads
+                .iter()
+                .filter(|upload| !upload.retrying)
+                .map(|upload| upload.next_attempt)
+                .min();
+            drop(uploads);
+
+            match next_attempt {
+                Some(next_attempt) => {
+                    tokio::select! {
+                        _ = time::sleep_until(next_attempt.into()) => {},
+                        _ = self.notify.notified() => {},
+                    }
+                }
+                None => self.notify.notified().await,
+            }
+        }
+    }
+
+    /// Put the upload back in line after a failed retry
+    async fn reschedule(&self, mut upload: PendingUpload) {
+        upload.schedule_retry();
+        upload.retrying = false;
+
+        let mut uploads = self.uploads.lock().await;
+
+        if let Some(entry) = uploads
+            .iter_mut()
+            .find(|entry| entry.video_path == upload.video_path)
+        {
+            *entry = upload;
+        }
+    }
+
+    async fn remove(&self, psql: &Database, video_path: &Path) {
+        self.uploads
+            .lock()
+            .await
+            .retain(|upload| upload.video_path != video_path);
+
+        if let Err(err) = psql.remove_pending_upload(video_path).await {
+            warn!("{err:?}");
+        }
+    }
+
+    /// Retry uploads in the background whenever they're due
+    pub fn process(ctx: Arc<Context>) {
+        tokio::spawn(async move {
+            loop {
+                let due = ctx.replay_queue.pending_uploads.next_due().await;
+
+                for upload in due {
+                    retry_upload(&ctx, upload).await;
+                }
+            }
+        });
+    }
+}
+
+async fn retry_upload(ctx: &Context, mut upload: PendingUpload) {
+    let beatmap_link = format!("https://osu.ppy.sh/beatmapsets/{}", upload.mapset_id);
+
+    let upload_res = {
+        let video = VideoUpload {
+            title: &upload.title,
+            author: upload.user,
+            path: &upload.video_path,
+            beatmap: &beatmap_link,
+            replay_hash: &upload.replay_hash,
+            progress: Arc::default(),
+        };
+
+        ctx.client().upload_video(&video).await
+    };
+
+    let content = match upload_res {
+        Ok(link) => {
+            info!("Uploaded pending video at {:?}", upload.video_path);
+
+            if let Some(ref fingerprint) = upload.fingerprint {
+                let render = DBRender {
+                    link: link.clone(),
+                    channel_id: None,
+                    message_id: None,
+                    guild_id: None,
+                };
+
+                let psql = ctx.psql();
+
+                if let Err(err) = psql
+                    .upsert_render(&upload.replay_hash, fingerprint, &render)
+                    .await
+                {
+                    warn!("{err:?}");
+                }
+            }
+
+            format!("<@{}> your replay is ready! {link}", upload.user)
+        }
+        Err(err) => {
+            upload.attempts += 1;
+
+            let rejection = err.rejection().map(str::to_owned);
+            let context = format!(
+                "failed to upload pending video at {:?} (attempt #{})",
+                upload.video_path, upload.attempts
+            );
+            warn!("{:?}", Report::from(err).wrap_err(context));
+
+            match rejection {
+                Some(text) => {
+                    remove_video(&upload.video_path).await;
+                    let text: String = text.chars().take(REJECTION_LIMIT).collect();
+
+                    format!(
+                        "<@{}> uploading your replay **{}** failed: `{text}`",
+                        upload.user, upload.title
+                    )
+                }
+                None if upload.attempts >= MAX_ATTEMPTS => {
+                    remove_video(&upload.video_path).await;
+
+                    format!(
+                        "<@{}> uploading your replay **{}** kept failing, giving up. \
+                        Please submit the replay again later.",
+                        upload.user, upload.title
+                    )
+                }
+                None => {
+                    let psql = ctx.psql();
+
+                    if let Err(err) = psql
+                        .update_pending_upload_attempts(&upload.video_path, upload.attempts)
+                        .await
+                    {
+                        warn!("{err:?}");
+                    }
+
+                    ctx.replay_queue.pending_uploads.reschedule(upload).await;
+
+                    return;
+                }
+            }
+        }
+    };
+
+    ctx.replay_queue
+        .pending_uploads
+        .remove(ctx.psql(), &upload.video_path)
+        .await;
+
+    // The title and link come from outside so the content is checked to not panic on send
+    if let Err(err) = twilight_validate::message::content(&content) {
+        let err = Report::from(err).wrap_err("invalid pending upload result");
+        warn!("{err:?}");
+
+        return;
+    }
+
+    let builder = MessageBuilder::new().content(content);
+
+    if let Err(err) = upload.output_channel.create_message(ctx, &builder).await {
+        let err = Report::from(err).wrap_err("failed to send pending upload result");
+        warn!("{err:?}");
+    }
+}
+
+/// The video won't be uploaded anymore and the user has to submit the replay again
+async fn remove_video(video_path: &Path) {
+    if let Err(err) = fs::remove_file(video_path).await {
+        let context = format!("failed to remove video of given up upload at {video_path:?}");
+        warn!("{:?}", Report::from(err).wrap_err(context));
+    }
+}
@@ -1,434 +1,469 @@
 use std::{
-    error::Error as StdError,
+    env,
     ffi::OsStr,
-    fmt::{Display, Formatter, Result as FmtResult},
-    fs,
-    io::Cursor,
-    path::PathBuf,
-    process::Stdio,
+    path::{Path, PathBuf},
+    process::{Command as StdCommand, Stdio},
     sync::Arc,
+    time::Duration,
 };
 
-use bytes::Bytes;
 use eyre::{Context as _, ContextCompat, Report, Result};
-use futures::future;
 use rosu_pp::{Beatmap, BeatmapExt};
 use rosu_v2::prelude::{Beatmap as Map, GameMods};
-use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
-    process::{ChildStdout, Command},
+use serde_json::json;
+use tokio::{fs, time};
+use twilight_model::{
+    guild::PremiumTier,
+    id::{marker::GuildMarker, Id},
 };
-use zip::ZipArchive;
 
 use crate::{
-    core::{BotConfig, Context, ReplayStatus},
-    util::{builder::MessageBuilder, levenshtein_similarity, ChannelExt},
+    core::{mapset_cache::mapset_path, BotConfig, Context, ReplayStatus},
+    custom_client::VideoUpload,
+    database::DBRender,
+    util::{builder::MessageBuilder, ChannelExt},
 };
 
-use super::{ReplayData, ReplayQueue, ReplaySlim};
+use super::{
+    danser::{danser_timeout, run_danser},
+    dedup::settings_name,
+    download::download_mapset,
+    render_fingerprint, ActiveReplay, Cancellation, PendingUpload, PendingUploads, ProgressTracker,
+    RenderError, ReplayData, ReplayQueue, ReplaySlim, TimePoints,
+};
 
 impl ReplayQueue {
     pub fn process(ctx: Arc<Context>) {
-        tokio::spawn(Self::async_process(ctx));
-    }
-
-    async fn async_process(ctx: Arc<Context>) {
-        let config = BotConfig::get();
-        let mut danser_path = config.paths.danser().to_owned();
-        danser_path.push("danser");
-
-        loop {
-            let ReplayData {
-                input_channel,
-                output_channel,
-                pitch,
-                path,
-                replay,
-                time_points,
-                user,
-            } = ctx.replay_queue.peek().await;
-
-            let replay_hash = match replay.replay_hash.as_deref() {
-                Some(replay_hash) => replay_hash,
-                None => {
-                    warn!("replay without replay hash");
-
-                    let content = "Could not get the replay hash";
-                    let _ = input_channel.error(&ctx, content).await;
-
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            };
-
-            let mapset_id = match replay.beatmap_hash.as_deref() {
-                Some(hash) => match ctx.osu().beatmap().checksum(hash).await {
-                    Ok(Map { mapset, .. }) => match mapset {
-                        Some(mapset) => mapset.mapset_id,
-                        None => {
-                            warn!("map without mapset");
-
-                            let content = "The mapset was not received when requesting the map from the osu!api";
-                            let _ = input_channel.error(&ctx, content).await;
-
-                            ctx.replay_queue.reset_peek().await;
-                            continue;
-                        }
-                    },
-                    Err(err) => {
-                        let context = format!("failed to request map with hash `{hash}`");
-                        let err = Report::from(err).wrap_err(context);
-                        warn!("{err:?}");
-
-                        let content = "Failed to retrieve map. Maybe it's not submitted?";
-                        let _ = input_channel.error(&ctx, content).await;
+        let workers = ctx.replay_queue.workers.len();
+        info!("Starting {workers} render worker(s)");
 
-                        ctx.replay_queue.reset_peek().await;
-                        continue;
-                    }
-                },
-                None => {
-                    warn!("missing hash in replay requested by user {user}");
+        for worker in 0..workers {
+            tokio::spawn(Self::async_process(Arc::clone(&ctx), worker));
+        }
 
-                    let content = "Missing the beatmap hash in the replay file";
-                    let _ = input_channel.error(&ctx, content).await;
+        // Retry failed uploads in the background
+        PendingUploads::process(ctx);
+    }
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            };
-            info!("Started map download");
-            ctx.replay_queue.set_status(ReplayStatus::Downloading).await;
+    async fn async_process(ctx: Arc<Context>, worker: usize) {
+        loop {
+            let ActiveReplay { data, cancel, .. } = ctx.replay_queue.next(worker).await;
+            let mut progress = ProgressTracker::new(worker, &data);
 
-            if let Err(err) = download_mapset(&ctx, mapset_id).await {
+            if let Err(err) = ctx.psql().set_queued_replay_in_progress(data.id).await {
                 warn!("{err:?}");
-
-                let content = "Failed to download map. Mirrors are likely down, try again later.";
-                let _ = input_channel.error(&ctx, content).await;
-
-                ctx.replay_queue.reset_peek().await;
-                continue;
             }
 
-            info!("Finished map download");
-
-            let mut settings_path = config.paths.danser().to_owned();
-            settings_path.push(format!("settings/{user}.json"));
+            match render(&ctx, &data, &cancel, &mut progress).await {
+                Ok(RenderedVideo::Attached(link)) => {
+                    ctx.stats.renders.record_success();
+                    progress.done(&ctx, &link).await;
 
-            let settings = if settings_path.exists() {
-                user.to_string()
-            } else {
-                "default".to_owned()
-            };
+                    ctx.replay_queue.complete(ctx.psql(), worker).await;
+                }
+                Ok(RenderedVideo::Uploaded(link)) => {
+                    ctx.stats.renders.record_success();
+                    progress.done(&ctx, &link).await;
 
-            let filename_opt = path
-                .file_name()
-                .and_then(OsStr::to_str)
-                .and_then(|name| name.split('.').next());
+                    let content = format!("<@{}> your replay is ready! {link}", data.user);
+                    let builder = MessageBuilder::new().content(content);
 
-            let filename = match filename_opt {
-                Some(name) => name,
-                None => {
-                    warn!("replay path `{path:?}` has an unexpected form");
+                    if let Err(err) = data.output_channel.create_message(&ctx, &builder).await {
+                        let err = Report::from(err).wrap_err("failed to send video link");
+                        warn!("{err:?}");
+                    }
 
-                    let content = "There was an error resolving the beatmap path";
-                    let _ = input_channel.error(&ctx, content).await;
+                    ctx.replay_queue.complete(ctx.psql(), worker).await;
+                }
+                Err(RenderError::Cancelled) => {
+                    info!("Replay was cancelled");
+                    data.remove_files().await;
+                    ctx.stats.renders.record_cancel();
+                    progress.cancelled(&ctx).await;
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
+                    ctx.replay_queue.finish(ctx.psql(), worker).await;
                 }
-            };
-
-            let mut command = Command::new(&danser_path);
-
-            command
-                .arg("-noupdatecheck")
-                .arg("-replay")
-                .arg(&path)
-                .arg("-record")
-                .arg("-settings")
-                .arg(settings)
-                .arg("-quickstart")
-                .arg("-out")
-                .arg(filename)
-                .arg("-preciseprogress")
-                .stderr(Stdio::piped())
-                .stdout(Stdio::piped());
-
-            if time_points.start != 0 {
-                command.args(["-start", &time_points.start.to_string()]);
-            }
+                Err(err) => {
+                    ctx.stats.renders.record_failure(err.kind());
+                    let content = format!("{}\nJob id: `{}`", err.user_message(), data.id);
+                    let context = format!("job {} failed", data.id);
+                    warn!("{:?}", err.into_report().wrap_err(context));
 
-            if time_points.end != 0 {
-                command.args(["-end", &time_points.end.to_string()]);
-            }
+                    progress.failed(&ctx, content).await;
 
-            if let Some(pitch) = pitch {
-                command.args(["-pitch", &pitch.to_string()]);
+                    ctx.replay_queue.finish(ctx.psql(), worker).await;
+                }
             }
+        }
+    }
+}
 
-            info!("Started replay processing");
-
-            ctx.replay_queue
-                .set_status(ReplayStatus::Rendering(0))
-                .await;
-
-            match command.spawn() {
-                Ok(mut child) => {
-                    let stdout = child.stdout.take().expect("missing stdout on child");
-                    let reader = BufReader::new(stdout);
-
-                    tokio::select! {
-                        _ = read_danser_progress(&ctx, reader) => unreachable!(),
-                        child_res = child.wait() => {
-                            trace!("Danser finished, stopped checking its logs");
-
-                            if let Err(err) = child_res {
-                                let err = Report::from(err).wrap_err("failed to run danser command");
-                                warn!("{err:?}");
+/// Where the finished video ended up
+enum RenderedVideo {
+    /// Posted as attachment in the output channel, contains the link to the message
+    Attached(String),
+    /// Uploaded to an external host, contains the link to the video
+    Uploaded(String),
+}
 
-                                let content = "Failed to run danser on the replay";
-                                let _ = input_channel.error(&ctx, content).await;
+/// Process the replay from start to finish and return where the video ended up
+async fn render(
+    ctx: &Context,
+    data: &ReplayData,
+    cancel: &Cancellation,
+    progress: &mut ProgressTracker,
+) -> Result<RenderedVideo, RenderError> {
+    let replay_hash = data
+        .replay
+        .replay_hash
+        .as_deref()
+        .ok_or(RenderError::MissingReplayHash)?;
+
+    let map_hash = data
+        .replay
+        .beatmap_hash
+        .as_deref()
+        .ok_or(RenderError::MissingMapHash)?;
+
+    let mapset_id = request_mapset_id(ctx, map_hash).await?;
+
+    // Other workers may neither download nor replace the mapset while it's being checked
+    let download_guard = ctx.mapsets.lock_download(mapset_id).await;
+
+    // Keep the mapset from being evicted until the replay is done
+    let lease = ctx.mapsets.lease(mapset_id);
+
+    let map_file = match find_map_file(mapset_id, map_hash).await {
+        Ok(map_file) => {
+            info!("Using cached mapset {mapset_id}");
+
+            map_file
+        }
+        Err(_) => {
+            // The mapset was downloaded before but the map got updated since.
+            // The outdated folder is only replaced once the new version is ready.
+            if mapset_path(mapset_id).exists() {
+                info!("No map file of cached mapset {mapset_id} matches the hash, re-downloading");
+            }
 
-                                ctx.replay_queue.reset_peek().await;
-                                continue;
-                            }
+            info!("Started map download");
+            progress.set_status(ctx, ReplayStatus::Downloading).await;
 
-                            if let Some(mut stderr) = child.stderr {
-                                let mut res = String::new();
+            let download_res = download_mapset(ctx, &lease, mapset_id, map_hash).await;
 
-                                trace!("Reading danser stderr...");
+            // Also update the size if the download failed since the outdated mapset may have been removed
+            ctx.mapsets.update_size(mapset_id).await;
+            let map_file = download_res?;
 
-                                if stderr.read_to_string(&mut res).await.is_ok() {
-                                    warn!("danser stderr: {res}");
-                                }
+            info!("Finished map download");
 
-                                trace!("Finished danser stderr");
-                            }
-                        },
-                    }
-                }
-                Err(err) => {
-                    let err = Report::from(err).wrap_err("failed to start danser command");
-                    warn!("{err:?}");
+            map_file
+        }
+    };
 
-                    let content = "Failed to run danser on the replay";
-                    let _ = input_channel.error(&ctx, content).await;
+    drop(download_guard);
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            }
+    if cancel.is_cancelled() {
+        return Err(RenderError::Cancelled);
+    }
 
-            info!("Finished replay processing");
+    let (command, video_path) = danser_command(data);
 
-            let title = match get_title() {
-                Ok(title) => title,
-                Err(err) => {
-                    warn!("{err:?}");
+    // Settings might change while rendering so the fingerprint is taken beforehand
+    let fingerprint = render_fingerprint(data.user, data.time_points, data.pitch).await;
 
-                    let content = "Failed to read danser logs";
-                    let _ = input_channel.error(&ctx, content).await;
+    info!("Started replay processing");
+    progress.set_status(ctx, ReplayStatus::Rendering(0)).await;
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            };
+    let timeout = danser_timeout(data);
+    run_danser(ctx, progress, command, cancel, timeout, data.id).await?;
 
-            let map_osu_file = match get_beatmap_osu_file(mapset_id, &title).await {
-                Ok(osu_file) => osu_file,
-                Err(err) => {
-                    let err = err.wrap_err("failed to get map_osu_file");
-                    warn!("{err:?}");
+    info!("Finished replay processing");
 
-                    let content = "danser did not like the replay file";
-                    let _ = input_channel.error(&ctx, content).await;
+    let video_title = video_title(data, map_file).await?;
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            };
+    // Past this point the replay can't be cancelled anymore
+    if !ctx.replay_queue.start_upload(progress.worker()).await {
+        return Err(RenderError::Cancelled);
+    }
 
-            let mut map_path = config.paths.songs();
-            map_path.push(format!("{mapset_id}/{map_osu_file}"));
+    progress.set_status(ctx, ReplayStatus::Uploading(0)).await;
 
-            let video_title = match create_title(&replay, map_path, &title).await {
-                Ok(title) => title,
-                Err(err) => {
-                    let err = err.wrap_err("failed to create title");
-                    warn!("{err:?}");
+    if let Some(render) = attach_video(ctx, data, &video_title, &video_path).await {
+        info!("Posted video as attachment");
+        store_render(ctx, replay_hash, &fingerprint, &render).await;
 
-                    let content = "There was an error while trying to create the video title";
-                    let _ = input_channel.error(&ctx, content).await;
+        return Ok(RenderedVideo::Attached(render.link));
+    }
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            };
+    info!("Started upload");
 
-            let mut file_path = config.paths.replays();
-            file_path.push(format!("{filename}.mp4"));
+    let beatmap_link = format!("https://osu.ppy.sh/beatmapsets/{mapset_id}");
 
-            info!("Started upload to shisha.mezo.xyz");
-            ctx.replay_queue.set_status(ReplayStatus::Uploading).await;
+    let video = VideoUpload {
+        title: &video_title,
+        author: data.user,
+        path: &video_path,
+        beatmap: &beatmap_link,
+        replay_hash,
+        progress: Arc::default(),
+    };
 
-            let beatmap_link = &format!("https://osu.ppy.sh/beatmapsets/{}", &mapset_id);
-            let upload_fut =
-                ctx.client()
-                    .upload_video(&video_title, user, file_path, beatmap_link, replay_hash);
+    let upload_fut = ctx.client().upload_video(&video);
+    tokio::pin!(upload_fut);
 
-            let link = match upload_fut.await {
-                Ok(res) if res.error == 1 => {
-                    let err = format!("failed to upload: `{}`", res.text);
-                    warn!("{err}");
+    let mut interval = time::interval(UPLOAD_PROGRESS_INTERVAL);
 
-                    let _ = input_channel.error(&ctx, err).await;
+    let upload_res = loop {
+        tokio::select! {
+            res = &mut upload_fut => break res,
+            _ = interval.tick() => {
+                let status = ReplayStatus::Uploading(video.progress.percent());
+                progress.set_status(ctx, status).await;
+            }
+        }
+    };
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-                Ok(res) => res.text,
-                Err(err) => {
-                    let err = err.wrap_err("failed to upload file");
-                    warn!("{err:?}");
+    let link = match upload_res {
+        Ok(link) => link,
+        Err(err) => match err.rejection() {
+            Some(text) => return Err(RenderError::UploadRejected(text.to_owned())),
+            None => {
+                // Keep the video so its upload can be retried without rendering again
+                let upload = PendingUpload::new(
+                    video_path.clone(),
+                    data.user,
+                    data.output_channel,
+                    video_title.clone(),
+                    mapset_id,
+                    replay_hash.to_owned(),
+                    fingerprint.clone(),
+                );
+
+                ctx.replay_queue
+                    .pending_uploads
+                    .add(ctx.psql(), upload)
+                    .await;
+
+                return Err(RenderError::UploadPending(Report::from(err)));
+            }
+        },
+    };
 
-                    let content = "Failed to upload file";
-                    let _ = input_channel.error(&ctx, content).await;
+    // The link is posted as is so it must neither break nor bloat the message
+    if !is_valid_link(&link) {
+        return Err(RenderError::InvalidLink(link));
+    }
 
-                    ctx.replay_queue.reset_peek().await;
-                    continue;
-                }
-            };
+    info!("Finished upload");
 
-            info!("Finished upload to shisha.mezo.xyz");
+    let render = DBRender {
+        link,
+        channel_id: None,
+        message_id: None,
+        guild_id: None,
+    };
 
-            let content = format!("<@{user}> your replay is ready! {link}");
-            let builder = MessageBuilder::new().content(content);
+    store_render(ctx, replay_hash, &fingerprint, &render).await;
 
-            if let Err(err) = output_channel.create_message(&ctx, &builder).await {
-                let err = Report::from(err).wrap_err("failed to send video link");
-                warn!("{err:?}");
-            }
+    Ok(RenderedVideo::Uploaded(render.link))
+}
 
-            ctx.replay_queue.reset_peek().await;
-        }
+/// Remember the render so identical replays can reuse it
+async fn store_render(ctx: &Context, replay_hash: &str, fingerprint: &str, render: &DBRender) {
+    if let Err(err) = ctx
+        .psql()
+        .upsert_render(replay_hash, fingerprint, render)
+        .await
+    {
+        warn!("{err:?}");
     }
 }
 
-async fn read_danser_progress(ctx: &Context, reader: BufReader<ChildStdout>) {
-    async fn inner(ctx: &Context, reader: BufReader<ChildStdout>) -> Result<()> {
-        let mut lines = reader.lines();
-        let mut started_encoding = false;
+/// How often the upload progress is checked
+const UPLOAD_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
 
-        trace!("Start reading danser logs...");
+/// Maximum attachment size in guilds without boosts and in DMs
+const DEFAULT_UPLOAD_LIMIT: u64 = 8 * 1024 * 1024;
 
-        loop {
-            trace!("Next danser log line...");
+/// Attachments have to be read into memory as a whole so larger videos are uploaded instead
+const MAX_ATTACHMENT_SIZE: u64 = 25 * 1024 * 1024;
 
-            let line_opt = lines
-                .next_line()
-                .await
-                .context("failed to read line of danser's stdout")?;
+/// How large attachments may be in the guild
+fn upload_limit(ctx: &Context, guild: Option<Id<GuildMarker>>) -> u64 {
+    let tier = guild.and_then(|guild| ctx.cache.guild(guild, |g| g.premium_tier()).ok());
 
-            let trimmed_line = line_opt
-                .as_deref()
-                .map(str::trim_end)
-                .filter(|line| !line.is_empty());
+    let limit = match tier {
+        Some(PremiumTier::Tier2) => 50 * 1024 * 1024,
+        Some(PremiumTier::Tier3) => 100 * 1024 * 1024,
+        _ => DEFAULT_UPLOAD_LIMIT,
+    };
 
-            if let Some(line) = trimmed_line {
-                debug!("[DANSER]: {line}");
-            }
+    limit.min(MAX_ATTACHMENT_SIZE)
+}
 
-            let line = match line_opt {
-                Some(line) => line,
-                None => return Ok(()),
-            };
-
-            if let Some(idx) = line.find("Progress: ").map(|idx| idx + 10) {
-                if let Some(end) = line[idx..].find('%') {
-                    if let Ok(progress) = line[idx..idx + end].parse() {
-                        let status = if started_encoding {
-                            ReplayStatus::Encoding(progress)
-                        } else {
-                            ReplayStatus::Rendering(progress)
-                        };
-
-                        ctx.replay_queue.set_status(status).await;
-                    } else {
-                        debug!("failed to parse progress in line `{line}`");
-                    }
-                }
-            } else if line.contains("Starting encoding!") {
-                started_encoding = true;
-                let status = ReplayStatus::Encoding(0);
-                ctx.replay_queue.set_status(status).await;
-            }
+/// Post the video directly into the output channel if it's small enough.
+///
+/// Returns the render pointing to the message if the video was posted.
+async fn attach_video(
+    ctx: &Context,
+    data: &ReplayData,
+    title: &str,
+    video_path: &Path,
+) -> Option<DBRender> {
+    let size = match fs::metadata(video_path).await {
+        Ok(metadata) => metadata.len(),
+        Err(err) => {
+            let context = format!("failed to read metadata of video at {video_path:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
+
+            return None;
         }
-    }
+    };
 
-    if let Err(err) = inner(ctx, reader).await {
-        error!("{err:?}");
+    if size > upload_limit(ctx, data.guild) {
+        return None;
     }
 
-    warn!("Stopped reading danser logs");
+    let bytes = match fs::read(video_path).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let context = format!("failed to read video at {video_path:?}");
+            warn!("{:?}", Report::from(err).wrap_err(context));
 
-    future::pending::<()>().await;
+            return None;
+        }
+    };
 
-    unreachable!()
-}
+    let name = video_path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or("replay.mp4");
 
-#[derive(Debug)]
-struct MapsetDownloadError {
-    kitsu: Report,
-    chimu: Report,
-}
+    let content = format!("<@{}> your replay is ready!\n**{title}**", data.user);
+    let builder = MessageBuilder::new()
+        .content(content)
+        .attachment(name, bytes);
+
+    let res = match data.output_channel.create_message(ctx, &builder).await {
+        Ok(response) => response.model().await.map_err(Report::from),
+        Err(err) => Err(Report::from(err)),
+    };
 
-impl Display for MapsetDownloadError {
-    #[inline]
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "failed to download mapset:\n\
-            kitsu: {:?}\n\
-            chimu: {:?}",
-            self.kitsu, self.chimu
-        )
+    match res {
+        Ok(msg) => {
+            let guild = data
+                .guild
+                .map_or_else(|| "@me".to_owned(), |guild| guild.to_string());
+
+            let link = format!(
+                "https://discord.com/channels/{guild}/{}/{}",
+                msg.channel_id, msg.id
+            );
+
+            Some(DBRender {
+                link,
+                channel_id: Some(msg.channel_id.get() as i64),
+                message_id: Some(msg.id.get() as i64),
+                guild_id: data.guild.map(|guild| guild.get() as i64),
+            })
+        }
+        Err(err) => {
+            let err = err.wrap_err("failed to post video as attachment, uploading instead");
+            warn!("{err:?}");
+
+            None
+        }
     }
 }
 
-impl StdError for MapsetDownloadError {
-    #[inline]
-    fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        None
+async fn request_mapset_id(ctx: &Context, hash: &str) -> Result<u32, RenderError> {
+    match ctx.osu().beatmap().checksum(hash).await {
+        Ok(Map { mapset, .. }) => match mapset {
+            Some(mapset) => Ok(mapset.mapset_id),
+            None => Err(RenderError::MissingMapset),
+        },
+        Err(err) => {
+            let context = format!("failed to request map with hash `{hash}`");
+
+            Err(RenderError::MapNotFound(
+                Report::from(err).wrap_err(context),
+            ))
+        }
     }
 }
 
-async fn download_mapset(ctx: &Context, mapset_id: u32) -> Result<()> {
-    let bytes = request_mapset(ctx, mapset_id).await?;
-    let cursor = Cursor::new(bytes);
+/// Prepare the danser command and return it along with the path of the resulting video
+fn danser_command(data: &ReplayData) -> (StdCommand, PathBuf) {
+    let config = BotConfig::get();
+    let mut danser_path = config.paths.danser().to_owned();
+    danser_path.push("danser");
+
+    // danser runs in the job's working directory so every path it gets must be absolute.
+    // The configured paths already are but replays restored from the database might not be.
+    let replay_path = env::current_dir().map_or_else(
+        |_| data.path.clone(),
+        |current_dir| current_dir.join(&data.path),
+    );
+
+    // Don't rely on the settings file to point to the right folders
+    let settings_patch = json!({
+        "General": {
+            "OsuSongsDir": config.paths.songs(),
+            "OsuSkinsDir": config.paths.skins(),
+        },
+        "Recording": {
+            "OutputDir": config.paths.replays(),
+        },
+    });
+
+    let mut command = StdCommand::new(danser_path);
+
+    command
+        .arg("-noupdatecheck")
+        .arg("-replay")
+        .arg(replay_path)
+        .arg("-record")
+        .arg("-settings")
+        .arg(settings_name(data.user))
+        .arg("-sPatch")
+        .arg(settings_patch.to_string())
+        .arg("-quickstart")
+        .arg("-out")
+        .arg(data.id.to_string())
+        .arg("-preciseprogress")
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped());
+
+    let TimePoints { start, end } = data.time_points;
+
+    if start != 0 {
+        command.args(["-start", &start.to_string()]);
+    }
 
-    let mut archive = ZipArchive::new(cursor).context("failed to create zip archive")?;
+    if end != 0 {
+        command.args(["-end", &end.to_string()]);
+    }
 
-    let mut out_path = BotConfig::get().paths.songs();
-    out_path.push(mapset_id.to_string());
+    if let Some(pitch) = data.pitch {
+        command.args(["-pitch", &pitch.to_string()]);
+    }
 
-    archive
-        .extract(&out_path)
-        .with_context(|| format!("failed to extract zip archive at `{out_path:?}`"))
+    (command, data.id.video_path())
 }
 
-async fn request_mapset(ctx: &Context, mapset_id: u32) -> Result<Bytes> {
-    let kitsu = match ctx.client().download_kitsu_mapset(mapset_id).await {
-        Ok(bytes) => return Ok(bytes),
-        Err(err) => err,
-    };
+fn is_valid_link(link: &str) -> bool {
+    const MAX_LEN: usize = 512;
 
-    let chimu = match ctx.client().download_chimu_mapset(mapset_id).await {
-        Ok(bytes) => return Ok(bytes),
-        Err(err) => err,
-    };  
+    link.len() <= MAX_LEN
+        && link.starts_with("https://")
+        && !link.contains(|c: char| c.is_whitespace() || c.is_control())
+}
 
-    Err(Report::from(MapsetDownloadError { kitsu, chimu }))
+async fn video_title(data: &ReplayData, map_file: MapFile) -> Result<String, RenderError> {
+    create_title(&data.replay, map_file.path, &map_file.title)
+        .await
+        .map_err(RenderError::Title)
 }
 
 async fn create_title(replay: &ReplaySlim, map_path: PathBuf, map_title: &str) -> Result<String> {
@@ -440,7 +475,6 @@ async fn create_title(replay: &ReplaySlim, map_path: PathBuf, map_title: &str) -
         .calculate()
         .stars();
 
-    // let map_title = get_title()?;
     let stars = (stars * 100.0).round() / 100.0;
     let player = replay.player_name.as_deref().unwrap_or("<unknown player>");
     let acc = replay.accuracy();
@@ -453,58 +487,84 @@ async fn create_title(replay: &ReplaySlim, map_path: PathBuf, map_title: &str) -
     Ok(format!("[{stars}⭐] {player} | {map_title} {mods}{acc}%"))
 }
 
-async fn get_beatmap_osu_file(mapset_id: u32, map_without_artist: &str) -> Result<String> {
-    let mut items_dir = BotConfig::get().paths.songs();
-    items_dir.push(mapset_id.to_string());
+/// The difficulty of a mapset that was played in a replay
+pub(super) struct MapFile {
+    path: PathBuf,
+    /// `Artist - Title [Version]`
+    title: String,
+}
 
-    let items = fs::read_dir(&items_dir)
-        .with_context(|| format!("failed to read items dir at {items_dir:?}"))?;
+/// Find the `.osu` file in the mapset's folder whose MD5 hash matches the given one
+pub(super) async fn find_map_file(mapset_id: u32, hash: &str) -> Result<MapFile> {
+    find_map_file_in(&mapset_path(mapset_id), hash).await
+}
 
-    let mut max_similarity = 0.0;
-    let mut final_file_name = String::new();
+/// Find the `.osu` file in the given folder whose MD5 hash matches the given one
+pub(super) async fn find_map_file_in(mapset_dir: &Path, hash: &str) -> Result<MapFile> {
+    let mut entries = fs::read_dir(&mapset_dir)
+        .await
+        .with_context(|| format!("failed to read mapset dir at {mapset_dir:?}"))?;
 
-    for entry in items {
-        match entry {
-            Ok(entry) => {
-                let file_name = entry.file_name();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .with_context(|| format!("failed to read entry in {mapset_dir:?}"))?
+    {
+        let path = entry.path();
 
-                if let Some(file_name) = file_name.to_str().filter(|name| name.ends_with(".osu")) {
-                    debug!("COMPARING: {map_without_artist} WITH: {file_name}");
+        let is_osu_file = path
+            .extension()
+            .filter(|ext| ext.eq_ignore_ascii_case("osu"))
+            .is_some();
 
-                    let similarity = levenshtein_similarity(map_without_artist, file_name);
+        if !is_osu_file {
+            continue;
+        }
 
-                    if similarity > max_similarity {
-                        max_similarity = similarity;
-                        final_file_name = file_name.to_owned();
-                    }
-                }
-            }
-            Err(err) => {
-                let context = format!("there was an error while reading files in {items_dir:?}");
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("failed to read map file at {path:?}"))?;
 
-                return Err(Report::from(err).wrap_err(context));
-            }
+        if format!("{:x}", md5::compute(&bytes)) != hash {
+            continue;
         }
-    }
 
-    debug!("FINAL TITLE: {final_file_name} SIMILARITY: {max_similarity}");
+        let title = map_title(&bytes)
+            .with_context(|| format!("missing metadata in map file at {path:?}"))?;
+
+        return Ok(MapFile { path, title });
+    }
 
-    Ok(final_file_name)
+    bail!("no map file in {mapset_dir:?} matches the hash `{hash}`")
 }
 
-fn get_title() -> Result<String> {
-    let mut logs_path = BotConfig::get().paths.danser().to_owned();
-    logs_path.push("danser.log");
+/// Title of the map based on the `[Metadata]` section of its `.osu` file
+fn map_title(bytes: &[u8]) -> Option<String> {
+    let content = String::from_utf8_lossy(bytes);
+
+    let mut in_metadata = false;
+    let mut artist = None;
+    let mut title = None;
+    let mut version = None;
 
-    let logs = fs::read_to_string(logs_path).context("failed to read danser logs")?;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_metadata = line == "[Metadata]";
+
+            continue;
+        } else if !in_metadata {
+            continue;
+        }
 
-    let line = logs
-        .lines()
-        .find(|line| line.contains("Playing:"))
-        .context("expected `Playing:` in danser logs")?;
+        if let Some((key, value)) = line.split_once(':') {
+            match key.trim_end() {
+                "Artist" => artist = Some(value.trim()),
+                "Title" => title = Some(value.trim()),
+                "Version" => version = Some(value.trim()),
+                _ => {}
+            }
+        }
+    }
 
-    line.splitn(4, ' ')
-        .last()
-        .map(str::to_owned)
-        .with_context(|| format!("expected at least 5 words in danser log line `{line}`"))
+    Some(format!("{} - {} [{}]", artist?, title?, version?))
 }
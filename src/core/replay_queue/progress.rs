@@ -0,0 +1,221 @@
+use std::{
+    slice,
+    time::{Duration, Instant},
+};
+
+use eyre::{Report, Result, WrapErr};
+use twilight_model::{
+    channel::embed::Embed,
+    id::{
+        marker::{ChannelMarker, MessageMarker},
+        Id,
+    },
+};
+
+use crate::{
+    core::Context,
+    util::{
+        builder::{EmbedBuilder, MessageBuilder},
+        constants::RED,
+        ChannelExt, MessageExt,
+    },
+};
+
+use super::{ReplayData, ReplayStatus};
+
+/// Interaction tokens are valid for 15 minutes, leave some leeway
+const TOKEN_LIFETIME: Duration = Duration::from_secs(14 * 60);
+
+/// Failure reasons are cut off after this many chars to keep the embed valid
+const REASON_LIMIT: usize = 1024;
+
+/// Minimum time between two edits while the stage stays the same
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Message that shows the requester how far along their replay is
+#[derive(Clone)]
+pub enum ProgressMessage {
+    /// The deferred response of the command that queued the replay
+    Interaction {
+        token: Box<str>,
+        created_at: Instant,
+    },
+    /// A regular message, used once the interaction token expired
+    Channel {
+        channel: Id<ChannelMarker>,
+        message: Id<MessageMarker>,
+    },
+}
+
+impl ProgressMessage {
+    pub fn interaction(token: &str) -> Self {
+        Self::Interaction {
+            token: token.into(),
+            created_at: Instant::now(),
+        }
+    }
+
+    async fn show(
+        &mut self,
+        ctx: &Context,
+        channel: Id<ChannelMarker>,
+        embed: Embed,
+    ) -> Result<()> {
+        // The message helpers panic on invalid embeds which would take the worker down with them
+        twilight_validate::embed::embed(&embed).wrap_err("invalid progress embed")?;
+
+        match self {
+            Self::Interaction { token, created_at } if created_at.elapsed() < TOKEN_LIFETIME => {
+                ctx.interaction()
+                    .update_response(token)
+                    .embeds(Some(slice::from_ref(&embed)))
+                    .wrap_err("invalid progress embed")?
+                    .exec()
+                    .await?;
+            }
+            Self::Interaction { .. } => {
+                let builder = MessageBuilder::new().embed(embed);
+                let msg = channel.create_message(ctx, &builder).await?.model().await?;
+
+                *self = Self::Channel {
+                    channel: msg.channel_id,
+                    message: msg.id,
+                };
+            }
+            Self::Channel { channel, message } => {
+                let builder = MessageBuilder::new().embed(embed);
+                (*message, *channel).update(ctx, &builder).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the worker's status up to date and shows it to the requester
+pub struct ProgressTracker {
+    worker: usize,
+    channel: Id<ChannelMarker>,
+    name: String,
+    message: Option<ProgressMessage>,
+    last_update: Option<(Instant, ReplayStatus)>,
+}
+
+impl ProgressTracker {
+    pub fn new(worker: usize, data: &ReplayData) -> Self {
+        Self {
+            worker,
+            channel: data.input_channel,
+            name: data.replay_name().to_owned(),
+            message: data.progress.clone(),
+            last_update: None,
+        }
+    }
+
+    pub fn worker(&self) -> usize {
+        self.worker
+    }
+
+    /// Update the worker's status and, unless that happened
+    /// just now already, edit the progress message.
+    pub async fn set_status(&mut self, ctx: &Context, status: ReplayStatus) {
+        ctx.replay_queue.set_status(self.worker, status).await;
+
+        let due = match self.last_update {
+            Some((at, prev)) => !prev.same_stage(status) || at.elapsed() >= UPDATE_INTERVAL,
+            None => true,
+        };
+
+        if !due {
+            return;
+        }
+
+        self.last_update = Some((Instant::now(), status));
+
+        let description = match status {
+            ReplayStatus::Waiting => "Starting...".to_owned(),
+            ReplayStatus::Downloading => "Downloading the map...".to_owned(),
+            ReplayStatus::Rendering(progress) => format!("Rendering... ({progress}%)"),
+            ReplayStatus::Encoding(progress) => format!("Encoding... ({progress}%)"),
+            ReplayStatus::Uploading(progress) => format!("Uploading the video... ({progress}%)"),
+        };
+
+        let embed = EmbedBuilder::new()
+            .title(&self.name)
+            .description(description)
+            .build();
+
+        self.show(ctx, embed).await;
+    }
+
+    /// Show the link to the finished video
+    pub async fn done(&mut self, ctx: &Context, link: &str) {
+        let embed = EmbedBuilder::new()
+            .title(&self.name)
+            .description(format!("Your replay is ready! {link}"))
+            .build();
+
+        self.show(ctx, embed).await;
+    }
+
+    pub async fn cancelled(&mut self, ctx: &Context) {
+        let embed = EmbedBuilder::new()
+            .title(&self.name)
+            .description("The replay was cancelled")
+            .build();
+
+        self.show(ctx, embed).await;
+    }
+
+    /// Show why the replay failed.
+    ///
+    /// If there is no progress message, the reason is sent to the input channel instead.
+    pub async fn failed(&mut self, ctx: &Context, reason: impl Into<String>) {
+        let reason: String = reason.into().chars().take(REASON_LIMIT).collect();
+
+        let embed = EmbedBuilder::new()
+            .title(&self.name)
+            .color(RED)
+            .description(reason)
+            .build();
+
+        if self.show(ctx, embed.clone()).await {
+            return;
+        }
+
+        // Sending an embed that didn't pass validation would panic
+        if let Err(err) = twilight_validate::embed::embed(&embed) {
+            let err = Report::from(err).wrap_err("invalid failure embed");
+            warn!("{err:?}");
+
+            return;
+        }
+
+        let builder = MessageBuilder::new().embed(embed);
+
+        if let Err(err) = self.channel.create_message(ctx, &builder).await {
+            let err = Report::from(err).wrap_err("failed to send failure reason");
+            warn!("{err:?}");
+        }
+    }
+
+    /// Returns whether the message was shown successfully.
+    ///
+    /// If editing fails, the message won't be used anymore.
+    async fn show(&mut self, ctx: &Context, embed: Embed) -> bool {
+        let message = match self.message {
+            Some(ref mut message) => message,
+            None => return false,
+        };
+
+        match message.show(ctx, self.channel, embed).await {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("{:?}", err.wrap_err("failed to update progress message"));
+                self.message = None;
+
+                false
+            }
+        }
+    }
+}
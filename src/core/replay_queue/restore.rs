@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use eyre::{Context as _, Result};
+use osu_db::Replay;
+use tokio::fs;
+use twilight_model::id::Id;
+
+use crate::{
+    core::Context,
+    database::DBQueuedReplay,
+    util::{builder::MessageBuilder, ChannelExt},
+};
+
+use super::{replay_name_from_file, JobId, ReplayData, ReplayQueue, ReplaySlim, TimePoints};
+
+impl ReplayQueue {
+    /// Re-queue all replays that were still pending or
+    /// in progress when the bot was last shut down.
+    ///
+    /// Must be called before [`ReplayQueue::process`].
+    pub async fn restore(&self, ctx: &Context) -> Result<()> {
+        let entries = ctx
+            .psql()
+            .queued_replays()
+            .await
+            .context("failed to get queued replays")?;
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let total = entries.len();
+        let mut restored = 0;
+
+        for entry in entries {
+            let DBQueuedReplay {
+                path,
+                job_id,
+                name,
+                user_id,
+                guild_id,
+                input_channel,
+                output_channel,
+                time_start,
+                time_end,
+                pitch,
+                in_progress,
+            } = entry;
+
+            let path = PathBuf::from(path);
+            let user = Id::new(user_id as u64);
+            let input_channel = Id::new(input_channel as u64);
+
+            let id: JobId = match job_id.parse() {
+                Ok(id) => id,
+                Err(_) => {
+                    warn!("Invalid job id `{job_id}` of queued replay at {path:?}, removing it");
+
+                    if let Err(err) = ctx.psql().remove_queued_replay(&job_id).await {
+                        warn!("{err:?}");
+                    }
+
+                    continue;
+                }
+            };
+
+            let replay = match parse_replay(&path).await {
+                Ok(replay) => replay,
+                Err(err) => {
+                    warn!("{:?}", err.wrap_err("failed to restore queued replay"));
+
+                    if let Err(err) = ctx.psql().remove_queued_replay(id).await {
+                        warn!("{err:?}");
+                    }
+
+                    let content = format!(
+                        "<@{user}> your replay could not be restored after a restart, \
+                        please submit it again."
+                    );
+
+                    let builder = MessageBuilder::new().content(content);
+                    let _ = input_channel.create_message(ctx, &builder).await;
+
+                    continue;
+                }
+            };
+
+            let name = name.unwrap_or_else(|| {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+                replay_name_from_file(&file_name)
+            });
+
+            let data = ReplayData {
+                id,
+                name,
+                guild: guild_id.map(|guild| Id::new(guild as u64)),
+                input_channel,
+                map_length: None,
+                output_channel: Id::new(output_channel as u64),
+                pitch,
+                path,
+                progress: None,
+                replay,
+                time_points: TimePoints {
+                    start: time_start as u32,
+                    end: time_end as u32,
+                },
+                user,
+            };
+
+            if in_progress {
+                let content = format!(
+                    "<@{user}> rendering your replay `{name}` was interrupted by a restart, \
+                    it has been put back into the queue.",
+                    name = data.replay_name(),
+                );
+
+                let builder = MessageBuilder::new().content(content);
+                let _ = input_channel.create_message(ctx, &builder).await;
+            }
+
+            self.queue.lock().await.push_back(data);
+            let _ = self.tx.send(());
+            restored += 1;
+        }
+
+        info!("Restored {restored}/{total} queued replays");
+
+        Ok(())
+    }
+}
+
+async fn parse_replay(path: &Path) -> Result<ReplaySlim> {
+    let bytes = fs::read(path)
+        .await
+        .with_context(|| format!("failed to read replay file at {path:?}"))?;
+
+    Replay::from_bytes(&bytes)
+        .map(ReplaySlim::from)
+        .with_context(|| format!("failed to parse replay at {path:?}"))
+}
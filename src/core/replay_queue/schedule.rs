@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+/// Who requested a replay, used to schedule replays fairly
+#[derive(Copy, Clone, Debug)]
+pub struct Requester {
+    pub user: Id<UserMarker>,
+    pub guild: Option<Id<GuildMarker>>,
+}
+
+#[derive(Default)]
+struct Rounds {
+    users: HashMap<Id<UserMarker>, usize>,
+    guilds: HashMap<Id<GuildMarker>, usize>,
+}
+
+impl Rounds {
+    /// How many replays of the user and of the guild were already counted.
+    ///
+    /// Replays from DMs are only grouped by their user.
+    fn key(&self, requester: Requester) -> (usize, usize) {
+        let user = self.users.get(&requester.user).copied().unwrap_or(0);
+
+        let guild = match requester.guild {
+            Some(guild) => self.guilds.get(&guild).copied().unwrap_or(0),
+            None => user,
+        };
+
+        (user, guild)
+    }
+
+    fn count(&mut self, requester: Requester) {
+        *self.users.entry(requester.user).or_default() += 1;
+
+        if let Some(guild) = requester.guild {
+            *self.guilds.entry(guild).or_default() += 1;
+        }
+    }
+}
+
+/// Index at which a new replay should be inserted into the queue.
+///
+/// Users are served round-robin, i.e. a user's n-th replay is placed
+/// after everyone else's n-th replay. Within the same round,
+/// guilds with fewer replays ahead of them come first.
+///
+/// `active` are the requesters of replays that are currently being processed
+/// and `queue` the requesters of all waiting replays in order.
+pub fn fair_position<A, Q>(active: A, queue: Q, new: Requester) -> usize
+where
+    A: IntoIterator<Item = Requester>,
+    Q: IntoIterator<Item = Requester>,
+{
+    let mut rounds = Rounds::default();
+
+    for requester in active {
+        rounds.count(requester);
+    }
+
+    let mut idx = 0;
+
+    for requester in queue {
+        if rounds.key(requester) > rounds.key(new) {
+            return idx;
+        }
+
+        rounds.count(requester);
+        idx += 1;
+    }
+
+    idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requester(user: u64, guild: Option<u64>) -> Requester {
+        Requester {
+            user: Id::new(user),
+            guild: guild.map(Id::new),
+        }
+    }
+
+    #[test]
+    fn empty_queue() {
+        let new = requester(1, Some(1));
+
+        assert_eq!(fair_position([], [], new), 0);
+    }
+
+    #[test]
+    fn round_robin_users() {
+        let queue = [
+            requester(1, Some(1)),
+            requester(1, Some(1)),
+            requester(1, Some(1)),
+        ];
+
+        assert_eq!(fair_position([], queue, requester(2, Some(2))), 1);
+        assert_eq!(fair_position([], queue, requester(1, Some(1))), 3);
+    }
+
+    #[test]
+    fn guild_breaks_ties() {
+        let queue = [
+            requester(1, Some(1)),
+            requester(2, Some(1)),
+            requester(1, Some(1)),
+        ];
+
+        assert_eq!(fair_position([], queue, requester(3, Some(2))), 1);
+        assert_eq!(fair_position([], queue, requester(3, None)), 1);
+        assert_eq!(fair_position([], queue, requester(3, Some(1))), 2);
+    }
+
+    #[test]
+    fn active_replays_count() {
+        let active = [requester(1, Some(1))];
+        let queue = [requester(2, Some(2))];
+
+        assert_eq!(fair_position(active, queue, requester(1, Some(1))), 1);
+        assert_eq!(fair_position([], queue, requester(1, Some(1))), 1);
+        assert_eq!(fair_position(active, queue, requester(3, Some(3))), 1);
+    }
+}
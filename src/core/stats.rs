@@ -1,14 +1,68 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
 use time::OffsetDateTime;
 
 pub struct BotStats {
     pub start_time: OffsetDateTime,
-    // TODO: pub replays_rendered: IntCounter (shisha.mezo.xyz endpoint or maybe local counter)
+    pub renders: RenderStats,
 }
 
 impl BotStats {
     pub fn new() -> Self {
         Self {
             start_time: OffsetDateTime::now_utc(),
+            renders: RenderStats::default(),
         }
     }
 }
+
+/// Outcomes of all renders since the bot started
+#[derive(Default)]
+pub struct RenderStats {
+    succeeded: AtomicU64,
+    cancelled: AtomicU64,
+    failed: Mutex<HashMap<&'static str, u64>>,
+}
+
+impl RenderStats {
+    pub fn record_success(&self) {
+        self.succeeded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cancel(&self) {
+        self.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, kind: &'static str) {
+        *self.failed.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    pub fn succeeded(&self) -> u64 {
+        self.succeeded.load(Ordering::Relaxed)
+    }
+
+    pub fn cancelled(&self) -> u64 {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Amount of failures per kind, most frequent first
+    pub fn failures(&self) -> Vec<(&'static str, u64)> {
+        let mut failures: Vec<_> = self
+            .failed
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(kind, count)| (*kind, *count))
+            .collect();
+
+        failures.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+
+        failures
+    }
+}
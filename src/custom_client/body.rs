@@ -0,0 +1,218 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use bytes::{Bytes, BytesMut};
+use eyre::{Context as _, Result};
+use hyper::{body::Sender, Body};
+use tokio::{fs::File, io::AsyncReadExt};
+
+/// Size of the chunks in which files are read and sent
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Request body whose files are only read from disk while it's being sent
+#[derive(Clone, Default)]
+pub struct StreamedBody {
+    parts: Vec<BodyPart>,
+    len: u64,
+}
+
+#[derive(Clone)]
+enum BodyPart {
+    Bytes(Bytes),
+    File(PathBuf),
+}
+
+impl StreamedBody {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Body that consists of a single file
+    pub async fn file(path: impl Into<PathBuf>) -> Result<Self> {
+        let mut body = Self::new();
+        body.push_file(path.into()).await?;
+
+        Ok(body)
+    }
+
+    pub fn push_bytes(&mut self, bytes: impl Into<Bytes>) {
+        let bytes = bytes.into();
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        self.len += bytes.len() as u64;
+        self.parts.push(BodyPart::Bytes(bytes));
+    }
+
+    pub async fn push_file(&mut self, path: PathBuf) -> Result<()> {
+        let metadata = tokio::fs::metadata(&path)
+            .await
+            .with_context(|| format!("failed to read metadata of {path:?}"))?;
+
+        self.len += metadata.len();
+        self.parts.push(BodyPart::File(path));
+
+        Ok(())
+    }
+
+    /// Total amount of bytes in the body
+    pub fn content_length(&self) -> u64 {
+        self.len
+    }
+
+    /// Send the body's parts in a background task, keeping track of the sent bytes
+    pub fn stream(&self, progress: Option<Arc<UploadProgress>>) -> Body {
+        let (mut sender, body) = Body::channel();
+
+        if let Some(ref progress) = progress {
+            progress.start(self.len);
+        }
+
+        let parts = self.parts.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = send_parts(&mut sender, parts, progress).await {
+                // Let the request fail instead of sending an incomplete body
+                sender.abort();
+                warn!("{:?}", err.wrap_err("failed to stream request body"));
+            }
+        });
+
+        body
+    }
+
+    #[cfg(test)]
+    pub fn into_parts(self) -> Vec<Result<Bytes, PathBuf>> {
+        self.parts
+            .into_iter()
+            .map(|part| match part {
+                BodyPart::Bytes(bytes) => Ok(bytes),
+                BodyPart::File(path) => Err(path),
+            })
+            .collect()
+    }
+}
+
+async fn send_parts(
+    sender: &mut Sender,
+    parts: Vec<BodyPart>,
+    progress: Option<Arc<UploadProgress>>,
+) -> Result<()> {
+    for part in parts {
+        match part {
+            BodyPart::Bytes(bytes) => {
+                let len = bytes.len() as u64;
+
+                // An error means the request was dropped, no need to keep sending
+                if sender.send_data(bytes).await.is_err() {
+                    return Ok(());
+                }
+
+                if let Some(ref progress) = progress {
+                    progress.add(len);
+                }
+            }
+            BodyPart::File(path) => {
+                let mut file = File::open(&path)
+                    .await
+                    .with_context(|| format!("failed to open file at {path:?}"))?;
+
+                loop {
+                    let mut chunk = BytesMut::with_capacity(CHUNK_SIZE);
+
+                    let read = file
+                        .read_buf(&mut chunk)
+                        .await
+                        .with_context(|| format!("failed to read from {path:?}"))?;
+
+                    if read == 0 {
+                        break;
+                    }
+
+                    if sender.send_data(chunk.freeze()).await.is_err() {
+                        return Ok(());
+                    }
+
+                    if let Some(ref progress) = progress {
+                        progress.add(read as u64);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How much of a request body was sent already
+#[derive(Default)]
+pub struct UploadProgress {
+    sent: AtomicU64,
+    total: AtomicU64,
+}
+
+impl UploadProgress {
+    pub fn percent(&self) -> u8 {
+        let total = self.total.load(Ordering::Relaxed);
+
+        if total == 0 {
+            return 0;
+        }
+
+        let sent = self.sent.load(Ordering::Relaxed);
+
+        (sent * 100 / total).min(100) as u8
+    }
+
+    fn start(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+        self.sent.store(0, Ordering::Relaxed);
+    }
+
+    fn add(&self, sent: u64) {
+        self.sent.fetch_add(sent, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process, sync::Arc};
+
+    use super::{StreamedBody, UploadProgress, CHUNK_SIZE};
+
+    #[tokio::test]
+    async fn stream_file() {
+        let mut path = env::temp_dir();
+        path.push(format!("shisha_body_test_{}.bin", process::id()));
+
+        // Spans multiple chunks and ends on a partial one
+        let content: Vec<u8> = (0..3 * CHUNK_SIZE + 123).map(|i| i as u8).collect();
+        tokio::fs::write(&path, &content).await.unwrap();
+
+        let mut body = StreamedBody::new();
+        body.push_bytes("head");
+        body.push_file(path.clone()).await.unwrap();
+        body.push_bytes("tail");
+
+        let progress = Arc::new(UploadProgress::default());
+        let stream = body.stream(Some(Arc::clone(&progress)));
+        let bytes = hyper::body::to_bytes(stream).await.unwrap();
+
+        let _ = tokio::fs::remove_file(&path).await;
+
+        let mut expected = b"head".to_vec();
+        expected.extend_from_slice(&content);
+        expected.extend_from_slice(b"tail");
+
+        assert_eq!(body.content_length(), expected.len() as u64);
+        assert_eq!(bytes.as_ref(), expected.as_slice());
+        assert_eq!(progress.percent(), 100);
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::VecDeque,
+    error::Error as StdError,
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use eyre::Report;
+use leaky_bucket_lite::LeakyBucket;
+
+use crate::core::MirrorConfig;
+
+use super::{ratelimiter, RetryPolicy};
+
+/// How many of the latest downloads are considered for a mirror's success rate
+const RECENT_DOWNLOADS: usize = 20;
+
+/// Consecutive failures after which a mirror is skipped for a while
+const FAILURE_THRESHOLD: usize = 3;
+
+/// How long a failing mirror is skipped
+const SKIP_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Site that provides mapset downloads
+pub struct MapsetMirror {
+    name: String,
+    url: String,
+    pub(super) ratelimiter: LeakyBucket,
+    pub(super) timeout: Duration,
+    health: Mutex<MirrorHealth>,
+}
+
+#[derive(Default)]
+struct MirrorHealth {
+    /// Whether the latest downloads succeeded, the most recent one being last
+    recent: VecDeque<bool>,
+    consecutive_failures: usize,
+    skipped_until: Option<Instant>,
+}
+
+impl MapsetMirror {
+    pub fn new(config: &MirrorConfig) -> Self {
+        Self {
+            name: mirror_name(&config.url),
+            url: config.url.clone(),
+            ratelimiter: ratelimiter(config.per_second),
+            timeout: config.timeout,
+            health: Mutex::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retries are short since there are other mirrors to fall back on
+    pub(super) fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: 2,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+            timeout: self.timeout,
+        }
+    }
+
+    pub(super) fn url(&self, mapset_id: u32) -> String {
+        self.url.replace("{mapset_id}", &mapset_id.to_string())
+    }
+
+    /// Whether the mirror failed too often recently and should not be used for now
+    pub fn is_skipped(&self) -> bool {
+        self.skipped_until().is_some()
+    }
+
+    /// Until when the mirror is skipped, if at all
+    pub fn skipped_until(&self) -> Option<Instant> {
+        self.health
+            .lock()
+            .unwrap()
+            .skipped_until
+            .filter(|until| *until > Instant::now())
+    }
+
+    /// Returns the fraction of recent downloads that succeeded
+    /// and how many downloads were considered for it.
+    pub fn success_rate(&self) -> (f32, usize) {
+        let health = self.health.lock().unwrap();
+        let total = health.recent.len();
+
+        if total == 0 {
+            return (1.0, 0);
+        }
+
+        let succeeded = health.recent.iter().filter(|&&success| success).count();
+
+        (succeeded as f32 / total as f32, total)
+    }
+
+    /// Keep track of whether a download from the mirror was successful
+    pub fn record(&self, success: bool) {
+        let mut health = self.health.lock().unwrap();
+
+        if health.recent.len() == RECENT_DOWNLOADS {
+            health.recent.pop_front();
+        }
+
+        health.recent.push_back(success);
+
+        if success {
+            health.consecutive_failures = 0;
+            health.skipped_until = None;
+        } else {
+            health.consecutive_failures += 1;
+
+            if health.consecutive_failures >= FAILURE_THRESHOLD {
+                warn!(
+                    "Mirror {} failed {} times in a row, skipping it for {SKIP_DURATION:?}",
+                    self.name, health.consecutive_failures
+                );
+
+                health.skipped_until = Some(Instant::now() + SKIP_DURATION);
+            }
+        }
+    }
+}
+
+/// Use the url's host as name of the mirror
+fn mirror_name(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+    without_scheme
+        .split('/')
+        .next()
+        .unwrap_or(without_scheme)
+        .to_owned()
+}
+
+/// All mirrors failed to provide the mapset
+#[derive(Debug)]
+pub struct MapsetDownloadError {
+    pub mapset_id: u32,
+    /// The mirror's name and why it failed
+    pub errors: Vec<(String, Report)>,
+}
+
+impl Display for MapsetDownloadError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "failed to download mapset {}:", self.mapset_id)?;
+
+        if self.errors.is_empty() {
+            return f.write_str(" no mirrors configured");
+        }
+
+        for (mirror, err) in self.errors.iter() {
+            write!(f, "\n{mirror}: {err:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StdError for MapsetDownloadError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        None
+    }
+}
@@ -1,10 +1,11 @@
-use std::{hash::Hash, path::Path};
+use std::{hash::Hash, path::Path, sync::Arc};
 
-use crate::core::BotConfig;
+use crate::core::{BotConfig, ClientConfig, SiteConfig};
 use bytes::Bytes;
 use eyre::{Context as _, Result};
 use http::{header::CONTENT_LENGTH, Response};
 use hyper::{
+    body::HttpBody,
     client::{connect::dns::GaiResolver, Client as HyperClient, HttpConnector},
     header::{CONTENT_TYPE, USER_AGENT},
     Body, Method, Request,
@@ -12,51 +13,125 @@ use hyper::{
 use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use leaky_bucket_lite::LeakyBucket;
 use serde::Deserialize;
-use tokio::time::Duration;
-use twilight_model::{
-    channel::Attachment,
-    id::{marker::UserMarker, Id},
+use tokio::{
+    fs::File,
+    io::AsyncWriteExt,
+    time::{self, Duration},
 };
+use twilight_model::channel::Attachment;
 
-use self::multipart::Multipart;
+pub use self::{
+    body::{StreamedBody, UploadProgress},
+    mirror::{MapsetDownloadError, MapsetMirror},
+    upload::{UploadBackend, VideoUpload},
+};
+
+use self::{
+    multipart::Multipart,
+    retry::{AttemptError, Idempotency, RetryPolicy},
+};
 
+mod body;
+mod mirror;
 mod multipart;
+mod retry;
+mod upload;
 
 static MY_USER_AGENT: &str = env!("CARGO_PKG_NAME");
 
+/// How long checking whether a video link is still valid may take
+const LINK_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Copy, Clone, Eq, Hash, PartialEq)]
 #[repr(u8)]
 enum Site {
     DiscordAttachment,
-    DownloadChimu,
-    DownloadKitsu,
     OsuReplay,
-    ShishaMezo,
+    Upload,
 }
 
-type Client = HyperClient<HttpsConnector<HttpConnector<GaiResolver>>, Body>;
+impl Site {
+    const ALL: [Site; 3] = [Site::DiscordAttachment, Site::OsuReplay, Site::Upload];
 
-pub struct CustomClient {
-    client: Client,
-    ratelimiters: [LeakyBucket; 5],
-    upload: UploadData,
+    fn name(self) -> &'static str {
+        match self {
+            Site::DiscordAttachment => "Discord attachments",
+            Site::OsuReplay => "osu! replays",
+            Site::Upload => "Video upload",
+        }
+    }
+
+    fn config(self, config: &ClientConfig) -> &SiteConfig {
+        match self {
+            Site::DiscordAttachment => &config.attachments,
+            Site::OsuReplay => &config.osu_replays,
+            Site::Upload => &config.upload,
+        }
+    }
+
+    fn retry_policy(self, timeout: Duration) -> RetryPolicy {
+        let (base_delay, max_delay) = match self {
+            Site::DiscordAttachment => (Duration::from_millis(500), Duration::from_secs(5)),
+            Site::OsuReplay => (Duration::from_secs(1), Duration::from_secs(10)),
+            Site::Upload => (Duration::from_secs(2), Duration::from_secs(30)),
+        };
+
+        RetryPolicy {
+            attempts: 3,
+            base_delay,
+            max_delay,
+            timeout,
+        }
+    }
+}
+
+/// Rate limit and retry policy of a [`Site`]
+struct SiteLimits {
+    ratelimiter: LeakyBucket,
+    policy: RetryPolicy,
+}
+
+impl SiteLimits {
+    fn new(site: Site, config: &ClientConfig) -> Self {
+        let config = site.config(config);
+
+        Self {
+            ratelimiter: ratelimiter(config.per_second),
+            policy: site.retry_policy(config.timeout),
+        }
+    }
 }
 
-struct UploadData {
-    secret: &'static str,
-    url: &'static str,
+/// Current state of a site's rate limit
+pub struct RatelimitState {
+    pub name: String,
+    /// Requests that can be made right now
+    pub tokens: u32,
+    /// Requests that can be made in a burst
+    pub max: u32,
+    pub timeout: Duration,
 }
 
-impl From<&'static BotConfig> for UploadData {
-    #[inline]
-    fn from(config: &'static BotConfig) -> Self {
+impl RatelimitState {
+    fn new(name: impl Into<String>, ratelimiter: &LeakyBucket, timeout: Duration) -> Self {
         Self {
-            secret: &config.tokens.upload_secret,
-            url: &config.upload_url,
+            name: name.into(),
+            tokens: ratelimiter.tokens(),
+            max: ratelimiter.max(),
+            timeout,
         }
     }
 }
 
+type Client = HyperClient<HttpsConnector<HttpConnector<GaiResolver>>, Body>;
+
+pub struct CustomClient {
+    client: Client,
+    sites: [SiteLimits; 3],
+    mirrors: Vec<MapsetMirror>,
+    uploads: Vec<UploadBackend>,
+}
+
 impl CustomClient {
     pub fn new() -> Self {
         let connector = HttpsConnectorBuilder::new()
@@ -67,54 +142,163 @@ impl CustomClient {
 
         let client = HyperClient::builder().build(connector);
 
-        let ratelimiter = |per_second| {
-            LeakyBucket::builder()
-                .max(per_second)
-                .tokens(per_second)
-                .refill_interval(Duration::from_millis(1000 / per_second as u64))
-                .refill_amount(1)
-                .build()
-        };
+        let config = BotConfig::get();
+        let sites = Site::ALL.map(|site| SiteLimits::new(site, &config.client));
+
+        let mirrors = config
+            .mapsets
+            .mirrors
+            .iter()
+            .map(MapsetMirror::new)
+            .collect();
 
-        let ratelimiters = [
-            ratelimiter(2), // DiscordAttachment
-            ratelimiter(1), // DownloadChimu
-            ratelimiter(1), // DownloadKitsu
-            ratelimiter(1), // OsuReplay
-            ratelimiter(1), // ShishaMezo
-        ];
+        let uploads = config
+            .upload
+            .backends
+            .iter()
+            .map(UploadBackend::new)
+            .collect();
 
         Self {
             client,
-            ratelimiters,
-            upload: UploadData::from(BotConfig::get()),
+            sites,
+            mirrors,
+            uploads,
         }
     }
 
-    async fn ratelimit(&self, site: Site) {
-        self.ratelimiters[site as usize].acquire_one().await
+    async fn make_get_request(
+        &self,
+        url: impl AsRef<str>,
+        site: Site,
+        idempotency: Idempotency,
+    ) -> Result<Bytes> {
+        let url = url.as_ref();
+        let SiteLimits {
+            ratelimiter,
+            policy,
+        } = &self.sites[site as usize];
+
+        let response = self
+            .send_get_request(url, ratelimiter, *policy, idempotency)
+            .await?;
+
+        read_body(response, policy.timeout).await
     }
 
-    async fn make_get_request(&self, url: impl AsRef<str>, site: Site) -> Result<Bytes> {
-        let url = url.as_ref();
+    async fn send_get_request(
+        &self,
+        url: &str,
+        ratelimiter: &LeakyBucket,
+        policy: RetryPolicy,
+        idempotency: Idempotency,
+    ) -> Result<Response<Body>> {
         trace!("GET request to url {url}");
 
-        let req = Request::builder()
-            .uri(url)
-            .method(Method::GET)
-            .header(USER_AGENT, MY_USER_AGENT)
-            .body(Body::empty())
-            .context("failed to build GET request")?;
+        let build = || {
+            Request::builder()
+                .uri(url)
+                .method(Method::GET)
+                .header(USER_AGENT, MY_USER_AGENT)
+                .body(Body::empty())
+                .context("failed to build GET request")
+        };
 
-        self.ratelimit(site).await;
+        self.send_with_retries(build, url, ratelimiter, policy, idempotency)
+            .await
+    }
 
+    /// Send the request until it succeeds or the policy says to give up
+    async fn send_with_retries<F>(
+        &self,
+        build: F,
+        url: &str,
+        ratelimiter: &LeakyBucket,
+        policy: RetryPolicy,
+        idempotency: Idempotency,
+    ) -> Result<Response<Body>>
+    where
+        F: Fn() -> Result<Request<Body>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let req = build()?;
+            ratelimiter.acquire_one().await;
+
+            let err = match time::timeout(policy.timeout, self.client.request(req)).await {
+                Ok(Ok(response)) => match AttemptError::check_status(response, url) {
+                    Ok(response) => return Ok(response),
+                    Err(err) => err,
+                },
+                Ok(Err(err)) => AttemptError::from_hyper(err),
+                Err(_) => AttemptError::timeout(policy.timeout),
+            };
+
+            let delay = match err.retry_delay(&policy, attempt - 1, idempotency) {
+                Some(delay) if attempt < policy.attempts => delay,
+                _ => return Err(err.into_report()),
+            };
+
+            debug!("Attempt #{attempt} of request to {url} failed, retrying in {delay:?}: {err}");
+
+            time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Stream the response body into a file, failing if it's larger than `max_size` bytes
+    async fn download_to_file(
+        &self,
+        url: &str,
+        ratelimiter: &LeakyBucket,
+        policy: RetryPolicy,
+        path: &Path,
+        max_size: u64,
+    ) -> Result<()> {
         let response = self
-            .client
-            .request(req)
+            .send_get_request(url, ratelimiter, policy, Idempotency::Idempotent)
+            .await?;
+
+        let content_len = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok())
+            .and_then(|len| len.parse::<u64>().ok());
+
+        if let Some(len) = content_len.filter(|&len| len > max_size) {
+            bail!("response of {len} bytes exceeds the limit of {max_size} bytes");
+        }
+
+        let mut file = File::create(path)
             .await
-            .context("failed to receive GET response")?;
+            .with_context(|| format!("failed to create file at {path:?}"))?;
+
+        let mut body = response.into_body();
+        let mut written = 0;
+
+        // Only a stalling body is aborted, slow but steady ones may take as long as they need
+        loop {
+            let chunk = match tokio::time::timeout(policy.timeout, body.data()).await {
+                Ok(Some(chunk)) => chunk.context("failed to receive response chunk")?,
+                Ok(None) => break,
+                Err(_) => bail!("no response data for {:?}", policy.timeout),
+            };
+
+            written += chunk.len() as u64;
+
+            if written > max_size {
+                bail!("response exceeds the limit of {max_size} bytes");
+            }
 
-        Self::error_for_status(response, url).await
+            file.write_all(&chunk)
+                .await
+                .with_context(|| format!("failed to write to file at {path:?}"))?;
+        }
+
+        file.flush()
+            .await
+            .with_context(|| format!("failed to flush file at {path:?}"))
     }
 
     async fn make_post_request(
@@ -122,6 +306,8 @@ impl CustomClient {
         url: impl AsRef<str>,
         site: Site,
         form: Multipart,
+        idempotency: Idempotency,
+        progress: Option<&Arc<UploadProgress>>,
     ) -> Result<Bytes> {
         let url = url.as_ref();
         trace!("POST request to url {url}");
@@ -129,38 +315,27 @@ impl CustomClient {
         let content_type = format!("multipart/form-data; boundary={}", form.boundary());
         let form = form.finish();
 
-        let req = Request::builder()
-            .method(Method::POST)
-            .uri(url)
-            .header(USER_AGENT, MY_USER_AGENT)
-            .header(CONTENT_TYPE, content_type)
-            .header(CONTENT_LENGTH, form.len())
-            .body(Body::from(form))
-            .context("failed to build POST request")?;
+        let build = || {
+            Request::builder()
+                .method(Method::POST)
+                .uri(url)
+                .header(USER_AGENT, MY_USER_AGENT)
+                .header(CONTENT_TYPE, &content_type)
+                .header(CONTENT_LENGTH, form.content_length())
+                .body(form.stream(progress.cloned()))
+                .context("failed to build POST request")
+        };
 
-        self.ratelimit(site).await;
+        let SiteLimits {
+            ratelimiter,
+            policy,
+        } = &self.sites[site as usize];
 
         let response = self
-            .client
-            .request(req)
-            .await
-            .context("failed to receive POST response")?;
-
-        Self::error_for_status(response, url).await
-    }
-
-    async fn error_for_status(response: Response<Body>, url: &str) -> Result<Bytes> {
-        let status = response.status();
-
-        if status.is_client_error() || status.is_server_error() {
-            bail!("failed with status code {status} when requesting {url}")
-        } else {
-            let bytes = hyper::body::to_bytes(response.into_body())
-                .await
-                .context("failed to extract response bytes")?;
+            .send_with_retries(build, url, ratelimiter, *policy, idempotency)
+            .await?;
 
-            Ok(bytes)
-        }
+        read_body(response, policy.timeout).await
     }
 
     pub async fn get_raw_replay(&self, score_id: u64) -> Result<Vec<u8>> {
@@ -174,7 +349,9 @@ impl CustomClient {
             content: String,
         }
 
-        let bytes = self.make_get_request(url, Site::OsuReplay).await?;
+        let bytes = self
+            .make_get_request(url, Site::OsuReplay, Idempotency::Idempotent)
+            .await?;
 
         let RawReplay { content } = serde_json::from_slice(&bytes).with_context(|| {
             let text = String::from_utf8_lossy(&bytes);
@@ -186,56 +363,117 @@ impl CustomClient {
     }
 
     pub async fn get_discord_attachment(&self, attachment: &Attachment) -> Result<Bytes> {
-        self.make_get_request(&attachment.url, Site::DiscordAttachment)
+        self.make_get_request(
+            &attachment.url,
+            Site::DiscordAttachment,
+            Idempotency::Idempotent,
+        )
+        .await
+    }
+
+    /// Check whether an uploaded video can still be reached
+    pub async fn is_link_valid(&self, url: &str) -> bool {
+        trace!("HEAD request to url {url}");
+
+        let build = || {
+            Request::builder()
+                .uri(url)
+                .method(Method::HEAD)
+                .header(USER_AGENT, MY_USER_AGENT)
+                .body(Body::empty())
+                .context("failed to build HEAD request")
+        };
+
+        let SiteLimits {
+            ratelimiter,
+            policy,
+        } = &self.sites[Site::Upload as usize];
+
+        let policy = RetryPolicy {
+            attempts: 1,
+            timeout: LINK_CHECK_TIMEOUT.min(policy.timeout),
+            ..*policy
+        };
+
+        match self
+            .send_with_retries(build, url, ratelimiter, policy, Idempotency::Idempotent)
             .await
+        {
+            Ok(_) => true,
+            Err(err) => {
+                debug!("{:?}", err.wrap_err("video link is no longer valid"));
+
+                false
+            }
+        }
     }
 
-    pub async fn download_chimu_mapset(&self, mapset_id: u32) -> Result<Bytes> {
-        let url = format!("https://chimu.moe/d/{mapset_id}");
+    /// Rate limit states of all sites and mirrors
+    pub fn ratelimits(&self) -> Vec<RatelimitState> {
+        let sites = Site::ALL.iter().map(|&site| {
+            let SiteLimits {
+                ratelimiter,
+                policy,
+            } = &self.sites[site as usize];
 
-        self.make_get_request(url, Site::DownloadChimu).await
+            RatelimitState::new(site.name(), ratelimiter, policy.timeout)
+        });
+
+        let mirrors = self
+            .mirrors
+            .iter()
+            .map(|mirror| RatelimitState::new(mirror.name(), &mirror.ratelimiter, mirror.timeout));
+
+        sites.chain(mirrors).collect()
+    }
+
+    pub fn mirrors(&self) -> &[MapsetMirror] {
+        &self.mirrors
     }
 
-    pub async fn download_kitsu_mapset(&self, mapset_id: u32) -> Result<Bytes> {
-        let url = format!("https://osu.direct/api/d/{mapset_id}");
+    /// The configured mirrors in the order they should be tried.
+    ///
+    /// Mirrors that keep failing come last so they're only used if all others fail too.
+    pub fn mirror_order(&self) -> Vec<&MapsetMirror> {
+        let (healthy, skipped): (Vec<_>, Vec<_>) =
+            self.mirrors.iter().partition(|mirror| !mirror.is_skipped());
 
-        self.make_get_request(url, Site::DownloadKitsu).await
+        healthy.into_iter().chain(skipped).collect()
     }
 
-    pub async fn upload_video(
+    /// Stream the mapset from the mirror into the given file
+    pub async fn download_mapset(
         &self,
-        title: &str,
-        author: Id<UserMarker>,
-        path: impl AsRef<Path>,
-        beatmap: &str,
-        hash: &str,
-    ) -> Result<UploadResponse> {
-        let form = Multipart::new()
-            .push_file("video", path)
+        mirror: &MapsetMirror,
+        mapset_id: u32,
+        path: &Path,
+        max_size: u64,
+    ) -> Result<()> {
+        let url = mirror.url(mapset_id);
+        let policy = mirror.retry_policy();
+
+        self.download_to_file(&url, &mirror.ratelimiter, policy, path, max_size)
             .await
-            .context("failed to create multipart form")?
-            .push_text("title", title)
-            .push_text("author", author)
-            .push_text("secret", self.upload.secret)
-            .push_text("hash", hash)
-            .push_text("beatmap", beatmap);
-
-        let bytes = self
-            .make_post_request(self.upload.url, Site::ShishaMezo, form)
-            .await?;
-
-        serde_json::from_slice(&bytes).with_context(|| {
-            let text = String::from_utf8_lossy(&bytes);
+    }
+}
 
-            format!("failed to deserialize upload response: {text}")
-        })
+/// Read the whole body, giving up if it's not complete within the timeout
+async fn read_body(response: Response<Body>, timeout: Duration) -> Result<Bytes> {
+    match time::timeout(timeout, hyper::body::to_bytes(response.into_body())).await {
+        Ok(res) => res.context("failed to extract response bytes"),
+        Err(_) => bail!("response body not received within {timeout:?}"),
     }
 }
 
-#[derive(Deserialize)]
-pub struct UploadResponse {
-    pub error: u16,
-    pub text: String,
+fn ratelimiter(per_second: u32) -> LeakyBucket {
+    LeakyBucket::builder()
+        .max(per_second)
+        .tokens(per_second)
+        .refill_interval(Duration::from_millis(
+            (1000 / per_second.max(1) as u64).max(1),
+        ))
+        .refill_amount(1)
+        .build()
 }
 
 #[derive(Deserialize)]
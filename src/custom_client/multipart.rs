@@ -1,13 +1,17 @@
-use std::{ffi::OsStr, fmt::Display, io::Write, path::Path};
+use std::{ffi::OsStr, fmt::Display, io::Write, mem, path::Path};
 
-use eyre::{Context as _, Result};
+use eyre::Result;
 use rand::{distributions::Alphanumeric, Rng};
-use tokio::{fs::File, io};
+
+use super::StreamedBody;
 
 const BOUNDARY_LEN: usize = 16;
 
+/// Multipart form whose files are streamed from disk once it's sent
 pub struct Multipart {
+    /// Everything that was written since the last file
     bytes: Vec<u8>,
+    body: StreamedBody,
     boundary: String,
 }
 
@@ -20,7 +24,8 @@ impl Multipart {
             .collect();
 
         Self {
-            bytes: Vec::with_capacity(1_048_576),
+            bytes: Vec::with_capacity(1024),
+            body: StreamedBody::new(),
             boundary,
         }
     }
@@ -50,28 +55,23 @@ impl Multipart {
         );
 
         let filename = path.file_name().and_then(OsStr::to_str);
-
-        let mut file = File::open(path)
-            .await
-            .with_context(|| format!("failed to open file at {path:?}"))?;
-
         self.write_field_headers(key, filename, Some("video/mp4"));
 
-        io::copy(&mut file, &mut self.bytes)
-            .await
-            .with_context(|| format!("failed to copy bytes from {path:?}"))?;
+        self.body.push_bytes(mem::take(&mut self.bytes));
+        self.body.push_file(path.to_owned()).await?;
 
         Ok(self)
     }
 
-    pub fn finish(mut self) -> Vec<u8> {
+    pub fn finish(mut self) -> StreamedBody {
         if !self.is_empty() {
             self.bytes.extend_from_slice(b"\r\n");
         }
 
         let _ = write!(self.bytes, "--{}--\r\n", self.boundary);
+        self.body.push_bytes(self.bytes);
 
-        self.bytes
+        self.body
     }
 
     pub fn boundary(&self) -> &str {
@@ -79,7 +79,7 @@ impl Multipart {
     }
 
     fn is_empty(&self) -> bool {
-        self.bytes.is_empty()
+        self.bytes.is_empty() && self.body.content_length() == 0
     }
 
     fn write_field_headers(
@@ -113,7 +113,17 @@ impl Multipart {
 
 #[cfg(test)]
 mod tests {
-    use super::Multipart;
+    use super::{Multipart, StreamedBody};
+
+    fn text(body: StreamedBody) -> String {
+        let bytes: Vec<u8> = body
+            .into_parts()
+            .into_iter()
+            .flat_map(|part| part.expect("unexpected file part"))
+            .collect();
+
+        String::from_utf8(bytes).unwrap()
+    }
 
     #[test]
     fn empty() {
@@ -121,7 +131,7 @@ mod tests {
 
         let expect = format!("--{}--\r\n", form.boundary());
 
-        let form = String::from_utf8(form.finish()).unwrap();
+        let form = text(form.finish());
 
         assert_eq!(form, expect);
     }
@@ -143,7 +153,7 @@ mod tests {
             value2\r\n--{boundary}--\r\n"
         );
 
-        let form = String::from_utf8(form.finish()).unwrap();
+        let form = text(form.finish());
 
         assert_eq!(form, expect);
     }
@@ -0,0 +1,285 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    time::Duration,
+};
+
+use eyre::Report;
+use http::{header::RETRY_AFTER, Response, StatusCode};
+use hyper::Body;
+use rand::Rng;
+
+/// Whether a request may be sent again after it possibly reached the server already
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Idempotency {
+    /// Sending the request multiple times has the same effect as sending it once
+    Idempotent,
+    /// The request is only sent again if the server surely did not process it
+    NonIdempotent,
+}
+
+/// How often and how patiently a request is attempted
+#[derive(Copy, Clone)]
+pub struct RetryPolicy {
+    /// Maximum amount of attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for every further retry
+    pub base_delay: Duration,
+    /// Upper bound for delays, also the longest `Retry-After` that will be waited for
+    pub max_delay: Duration,
+    /// How long a single attempt may take until a response arrives
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter
+    fn backoff(&self, retry: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1 << retry.min(16));
+        let max = exp.min(self.max_delay);
+
+        rand::thread_rng().gen_range(Duration::ZERO..=max)
+    }
+}
+
+/// Why a single attempt failed
+pub(super) enum AttemptError {
+    /// The request did not reach the server
+    NotSent(Report),
+    /// The request may or may not have been processed by the server
+    Unknown(Report),
+    /// The server responded with an error status
+    Status {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        url: Box<str>,
+    },
+}
+
+impl AttemptError {
+    pub(super) fn from_hyper(err: hyper::Error) -> Self {
+        if err.is_connect() {
+            Self::NotSent(Report::new(err).wrap_err("failed to connect"))
+        } else {
+            Self::Unknown(Report::new(err).wrap_err("failed to receive response"))
+        }
+    }
+
+    pub(super) fn timeout(timeout: Duration) -> Self {
+        Self::Unknown(eyre!("no response after {timeout:?}"))
+    }
+
+    /// Returns the response if its status is not an error
+    pub(super) fn check_status(
+        response: Response<Body>,
+        url: &str,
+    ) -> Result<Response<Body>, Self> {
+        let status = response.status();
+
+        if !(status.is_client_error() || status.is_server_error()) {
+            return Ok(response);
+        }
+
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+
+        Err(Self::Status {
+            status,
+            retry_after,
+            url: url.into(),
+        })
+    }
+
+    /// How long to wait before the next attempt, or `None` if the request should not be retried
+    pub(super) fn retry_delay(
+        &self,
+        policy: &RetryPolicy,
+        retry: u32,
+        idempotency: Idempotency,
+    ) -> Option<Duration> {
+        let idempotent = idempotency == Idempotency::Idempotent;
+
+        match self {
+            Self::NotSent(_) => Some(policy.backoff(retry)),
+            Self::Unknown(_) => idempotent.then(|| policy.backoff(retry)),
+            Self::Status {
+                status,
+                retry_after,
+                ..
+            } => {
+                // Too many requests and service unavailable mean the request was not processed
+                let retryable = match *status {
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+                    status => idempotent && status.is_server_error(),
+                };
+
+                if !retryable {
+                    return None;
+                }
+
+                match retry_after {
+                    Some(retry_after) if *retry_after > policy.max_delay => None,
+                    Some(retry_after) => Some((*retry_after).max(policy.backoff(retry))),
+                    None => Some(policy.backoff(retry)),
+                }
+            }
+        }
+    }
+
+    pub(super) fn into_report(self) -> Report {
+        match self {
+            Self::NotSent(err) | Self::Unknown(err) => err,
+            this @ Self::Status { .. } => Report::msg(this.to_string()),
+        }
+    }
+}
+
+impl Display for AttemptError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::NotSent(err) | Self::Unknown(err) => write!(f, "{err}"),
+            Self::Status { status, url, .. } => {
+                write!(f, "failed with status code {status} when requesting {url}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    fn response(status: u16, retry_after: Option<&str>) -> Response<Body> {
+        let mut builder = Response::builder().status(status);
+
+        if let Some(retry_after) = retry_after {
+            builder = builder.header(RETRY_AFTER, retry_after);
+        }
+
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn status_error(status: u16, retry_after: Option<&str>) -> AttemptError {
+        match AttemptError::check_status(response(status, retry_after), "url") {
+            Ok(_) => panic!("expected status {status} to be an error"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn backoff_within_bounds() {
+        let policy = policy();
+
+        for retry in 0..4 {
+            let max = policy.base_delay * (1 << retry);
+
+            for _ in 0..100 {
+                assert!(policy.backoff(retry) <= max);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_capped() {
+        let policy = policy();
+
+        for retry in [4, 16, 32, u32::MAX] {
+            for _ in 0..100 {
+                assert!(policy.backoff(retry) <= policy.max_delay);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_jitter() {
+        let policy = policy();
+        let first = policy.backoff(3);
+
+        // All 100 delays being equal is practically impossible with jitter
+        assert!((0..100).any(|_| policy.backoff(3) != first));
+    }
+
+    #[test]
+    fn success_status() {
+        assert!(AttemptError::check_status(response(200, Some("5")), "url").is_ok());
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        match status_error(429, Some(" 5 ")) {
+            AttemptError::Status { retry_after, .. } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(5)))
+            }
+            _ => panic!("expected status error"),
+        }
+    }
+
+    #[test]
+    fn retry_after_invalid() {
+        for value in ["Wed, 21 Oct 2015 07:28:00 GMT", "-1", "soon"] {
+            match status_error(503, Some(value)) {
+                AttemptError::Status { retry_after, .. } => assert_eq!(retry_after, None),
+                _ => panic!("expected status error"),
+            }
+        }
+    }
+
+    #[test]
+    fn retry_after_respected() {
+        let delay =
+            status_error(429, Some("8")).retry_delay(&policy(), 0, Idempotency::NonIdempotent);
+
+        assert!(delay >= Some(Duration::from_secs(8)));
+        assert!(delay <= Some(policy().max_delay));
+    }
+
+    #[test]
+    fn retry_after_too_long() {
+        let delay =
+            status_error(429, Some("60")).retry_delay(&policy(), 0, Idempotency::Idempotent);
+
+        assert_eq!(delay, None);
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        let policy = policy();
+
+        let retry = |status, idempotency| {
+            status_error(status, None)
+                .retry_delay(&policy, 0, idempotency)
+                .is_some()
+        };
+
+        assert!(retry(429, Idempotency::NonIdempotent));
+        assert!(retry(503, Idempotency::NonIdempotent));
+        assert!(retry(500, Idempotency::Idempotent));
+        assert!(!retry(500, Idempotency::NonIdempotent));
+        assert!(!retry(404, Idempotency::Idempotent));
+    }
+
+    #[test]
+    fn unknown_outcome_only_retried_if_idempotent() {
+        let policy = policy();
+        let err = AttemptError::timeout(policy.timeout);
+
+        assert!(err
+            .retry_delay(&policy, 0, Idempotency::Idempotent)
+            .is_some());
+        assert!(err
+            .retry_delay(&policy, 0, Idempotency::NonIdempotent)
+            .is_none());
+    }
+}
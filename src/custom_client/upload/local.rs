@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use eyre::{Context as _, Result};
+use tokio::fs;
+
+use super::{UploadFailure, VideoUpload};
+
+/// A folder whose files are served statically
+pub struct LocalBackend {
+    pub(super) dir: PathBuf,
+    pub(super) public_url: String,
+}
+
+impl LocalBackend {
+    pub(super) async fn upload(&self, video: &VideoUpload<'_>) -> Result<String, UploadFailure> {
+        self.copy_video(video).await.map_err(UploadFailure::Failed)
+    }
+
+    async fn copy_video(&self, video: &VideoUpload<'_>) -> Result<String> {
+        let file_name = video.file_name();
+
+        fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("failed to create folder at {:?}", self.dir))?;
+
+        let path = self.dir.join(&file_name);
+
+        fs::copy(video.path, &path)
+            .await
+            .with_context(|| format!("failed to copy video to {path:?}"))?;
+
+        Ok(format!("{}/{file_name}", self.public_url))
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    error::Error as StdError,
+    ffi::OsStr,
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::Path,
+    sync::Arc,
+};
+
+use eyre::Report;
+use twilight_model::id::{marker::UserMarker, Id};
+
+use crate::core::UploadBackendConfig;
+
+pub use self::{local::LocalBackend, s3::S3Backend, shisha::ShishaBackend};
+
+use super::{CustomClient, UploadProgress};
+
+mod local;
+mod s3;
+mod shisha;
+
+/// Somewhere to put rendered videos so they can be linked
+pub enum UploadBackend {
+    Shisha(ShishaBackend),
+    Local(LocalBackend),
+    S3(S3Backend),
+}
+
+impl UploadBackend {
+    pub fn new(config: &UploadBackendConfig) -> Self {
+        match config {
+            UploadBackendConfig::Shisha { url, secret } => Self::Shisha(ShishaBackend {
+                url: url.clone(),
+                secret: secret.clone(),
+            }),
+            UploadBackendConfig::Local { dir, public_url } => Self::Local(LocalBackend {
+                dir: dir.clone(),
+                public_url: public_url.trim_end_matches('/').to_owned(),
+            }),
+            UploadBackendConfig::S3 {
+                endpoint,
+                bucket,
+                region,
+                access_key,
+                secret_key,
+                public_url,
+            } => Self::S3(S3Backend {
+                endpoint: endpoint.trim_end_matches('/').to_owned(),
+                bucket: bucket.clone(),
+                region: region.clone(),
+                access_key: access_key.clone(),
+                secret_key: secret_key.clone(),
+                public_url: public_url.trim_end_matches('/').to_owned(),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Shisha(_) => "shisha",
+            Self::Local(_) => "local",
+            Self::S3(_) => "s3",
+        }
+    }
+}
+
+/// A rendered video and the information that goes along with it
+pub struct VideoUpload<'a> {
+    pub title: &'a str,
+    pub author: Id<UserMarker>,
+    pub path: &'a Path,
+    /// Link to the mapset
+    pub beatmap: &'a str,
+    pub replay_hash: &'a str,
+    /// Updated while the video is being sent
+    pub progress: Arc<UploadProgress>,
+}
+
+impl VideoUpload<'_> {
+    /// Name under which the video is stored.
+    ///
+    /// Videos are named after their job so that different renders of
+    /// the same replay don't overwrite each other's already shared links.
+    fn file_name(&self) -> String {
+        match self.path.file_name().and_then(OsStr::to_str) {
+            Some(name) => name.to_owned(),
+            None => format!("{}.mp4", self.replay_hash),
+        }
+    }
+}
+
+/// Why a single backend failed to take the video
+#[derive(Debug)]
+pub enum UploadFailure {
+    /// The backend refused the video and explained why
+    Rejected(String),
+    Failed(Report),
+}
+
+/// None of the backends took the video
+#[derive(Debug)]
+pub struct UploadError {
+    /// The backend's name and why it failed
+    pub errors: Vec<(&'static str, UploadFailure)>,
+}
+
+impl UploadError {
+    /// If all backends rejected the video, returns the first reason
+    pub fn rejection(&self) -> Option<&str> {
+        let mut rejection = None;
+
+        for (_, failure) in self.errors.iter() {
+            match failure {
+                UploadFailure::Rejected(text) => {
+                    rejection.get_or_insert(text.as_str());
+                }
+                UploadFailure::Failed(_) => return None,
+            }
+        }
+
+        rejection
+    }
+}
+
+impl Display for UploadError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("failed to upload video:")?;
+
+        if self.errors.is_empty() {
+            return f.write_str(" no upload backends configured");
+        }
+
+        for (backend, failure) in self.errors.iter() {
+            match failure {
+                UploadFailure::Rejected(text) => write!(f, "\n{backend}: rejected `{text}`")?,
+                UploadFailure::Failed(err) => write!(f, "\n{backend}: {err:?}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl StdError for UploadError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        None
+    }
+}
+
+impl CustomClient {
+    /// Try the configured upload backends in order and return the link of the first successful upload
+    pub async fn upload_video(&self, video: &VideoUpload<'_>) -> Result<String, UploadError> {
+        let mut errors = Vec::new();
+
+        for backend in self.uploads.iter() {
+            let res = match backend {
+                UploadBackend::Shisha(backend) => self.upload_to_shisha(backend, video).await,
+                UploadBackend::Local(backend) => backend.upload(video).await,
+                UploadBackend::S3(backend) => self.upload_to_s3(backend, video).await,
+            };
+
+            match res {
+                Ok(link) => {
+                    debug!("Uploaded video to {}", backend.name());
+
+                    return Ok(link);
+                }
+                Err(err) => errors.push((backend.name(), err)),
+            }
+        }
+
+        Err(UploadError { errors })
+    }
+}
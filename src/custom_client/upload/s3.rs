@@ -0,0 +1,180 @@
+use std::{fmt::Write, sync::Arc};
+
+use eyre::{Context as _, ContextCompat, Result};
+use hmac::{Hmac, Mac};
+use hyper::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, HOST, USER_AGENT},
+    Method, Request, Uri,
+};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::custom_client::{
+    CustomClient, Idempotency, Site, SiteLimits, StreamedBody, MY_USER_AGENT,
+};
+
+use super::{UploadFailure, VideoUpload};
+
+/// The payload is not hashed, S3 accepts that for `PUT` requests
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Any S3-compatible object store, addressed path-style
+pub struct S3Backend {
+    pub(super) endpoint: String,
+    pub(super) bucket: String,
+    pub(super) region: String,
+    pub(super) access_key: String,
+    pub(super) secret_key: String,
+    pub(super) public_url: String,
+}
+
+impl S3Backend {
+    /// Create the `Authorization` header of a request as per AWS Signature Version 4
+    fn authorization(&self, uri: &Uri, host: &str, now: OffsetDateTime) -> (String, String) {
+        let date = format!(
+            "{:04}{:02}{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day()
+        );
+
+        let timestamp = format!(
+            "{date}T{:02}{:02}{:02}Z",
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+
+        let canonical_request = format!(
+            "PUT\n\
+            {path}\n\
+            \n\
+            host:{host}\n\
+            x-amz-content-sha256:{UNSIGNED_PAYLOAD}\n\
+            x-amz-date:{timestamp}\n\
+            \n\
+            {signed_headers}\n\
+            {UNSIGNED_PAYLOAD}",
+            path = uri.path(),
+        );
+
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key,
+        );
+
+        (authorization, timestamp)
+    }
+}
+
+impl CustomClient {
+    pub(super) async fn upload_to_s3(
+        &self,
+        backend: &S3Backend,
+        video: &VideoUpload<'_>,
+    ) -> Result<String, UploadFailure> {
+        self.put_s3_object(backend, video)
+            .await
+            .map_err(UploadFailure::Failed)
+    }
+
+    async fn put_s3_object(&self, backend: &S3Backend, video: &VideoUpload<'_>) -> Result<String> {
+        let file_name = video.file_name();
+
+        let url = format!("{}/{}/{file_name}", backend.endpoint, backend.bucket);
+        let uri: Uri = url.parse().context("invalid S3 url")?;
+
+        let host = uri
+            .authority()
+            .context("missing host in S3 endpoint")?
+            .as_str()
+            .to_owned();
+
+        let body = StreamedBody::file(video.path).await?;
+
+        let build = || {
+            let (authorization, timestamp) =
+                backend.authorization(&uri, &host, OffsetDateTime::now_utc());
+
+            Request::builder()
+                .method(Method::PUT)
+                .uri(uri.clone())
+                .header(USER_AGENT, MY_USER_AGENT)
+                .header(HOST, &host)
+                .header(CONTENT_TYPE, "video/mp4")
+                .header(CONTENT_LENGTH, body.content_length())
+                .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+                .header("x-amz-date", timestamp)
+                .header("authorization", authorization)
+                .body(body.stream(Some(Arc::clone(&video.progress))))
+                .context("failed to build S3 PUT request")
+        };
+
+        let SiteLimits {
+            ratelimiter,
+            policy,
+        } = &self.sites[Site::Upload as usize];
+
+        self.send_with_retries(build, &url, ratelimiter, *policy, Idempotency::Idempotent)
+            .await?;
+
+        Ok(format!("{}/{file_name}", backend.public_url))
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{secret_key}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+
+    hmac(&key, b"aws4_request")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aws_signing_key_example() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+}
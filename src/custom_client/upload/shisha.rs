@@ -0,0 +1,62 @@
+use eyre::Context as _;
+use serde::Deserialize;
+
+use crate::custom_client::{multipart::Multipart, CustomClient, Idempotency, Site};
+
+use super::{UploadFailure, VideoUpload};
+
+/// The server behind shisha.mezo.xyz
+pub struct ShishaBackend {
+    pub(super) url: String,
+    pub(super) secret: String,
+}
+
+#[derive(Deserialize)]
+struct UploadResponse {
+    error: u16,
+    text: String,
+}
+
+impl CustomClient {
+    pub(super) async fn upload_to_shisha(
+        &self,
+        backend: &ShishaBackend,
+        video: &VideoUpload<'_>,
+    ) -> Result<String, UploadFailure> {
+        let form = Multipart::new()
+            .push_file("video", video.path)
+            .await
+            .context("failed to create multipart form")
+            .map_err(UploadFailure::Failed)?
+            .push_text("title", video.title)
+            .push_text("author", video.author)
+            .push_text("secret", &backend.secret)
+            .push_text("hash", video.replay_hash)
+            .push_text("beatmap", video.beatmap);
+
+        let bytes = self
+            .make_post_request(
+                &backend.url,
+                Site::Upload,
+                form,
+                Idempotency::NonIdempotent,
+                Some(&video.progress),
+            )
+            .await
+            .map_err(UploadFailure::Failed)?;
+
+        let response: UploadResponse = serde_json::from_slice(&bytes)
+            .with_context(|| {
+                let text = String::from_utf8_lossy(&bytes);
+
+                format!("failed to deserialize upload response: {text}")
+            })
+            .map_err(UploadFailure::Failed)?;
+
+        if response.error == 1 {
+            Err(UploadFailure::Rejected(response.text))
+        } else {
+            Ok(response.text)
+        }
+    }
+}
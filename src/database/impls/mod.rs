@@ -1 +1,4 @@
 mod blacklist;
+mod pending_upload;
+mod render;
+mod replay_queue;
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use eyre::{Result, WrapErr};
+
+use crate::{
+    core::replay_queue::PendingUpload,
+    database::{DBPendingUpload, Database},
+};
+
+impl Database {
+    pub async fn insert_pending_upload(&self, upload: &PendingUpload) -> Result<()> {
+        let query = sqlx::query(
+            "
+INSERT INTO pending_uploads (
+  video_path, user_id, output_channel, 
+  title, mapset_id, replay_hash, settings_hash, attempts
+) 
+VALUES 
+  ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (video_path) DO 
+UPDATE 
+SET 
+  user_id = EXCLUDED.user_id, 
+  output_channel = EXCLUDED.output_channel, 
+  title = EXCLUDED.title, 
+  mapset_id = EXCLUDED.mapset_id, 
+  replay_hash = EXCLUDED.replay_hash, 
+  settings_hash = EXCLUDED.settings_hash, 
+  attempts = EXCLUDED.attempts",
+        );
+
+        query
+            .bind(upload.video_path.to_string_lossy())
+            .bind(upload.user.get() as i64)
+            .bind(upload.output_channel.get() as i64)
+            .bind(&upload.title)
+            .bind(upload.mapset_id as i32)
+            .bind(&upload.replay_hash)
+            .bind(&upload.fingerprint)
+            .bind(upload.attempts as i32)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to insert pending upload")?;
+
+        Ok(())
+    }
+
+    pub async fn update_pending_upload_attempts(
+        &self,
+        video_path: &Path,
+        attempts: u32,
+    ) -> Result<()> {
+        let query = sqlx::query(
+            "UPDATE pending_uploads 
+            SET attempts = $2 
+            WHERE video_path = $1",
+        );
+
+        query
+            .bind(video_path.to_string_lossy())
+            .bind(attempts as i32)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to update attempts of pending upload")?;
+
+        Ok(())
+    }
+
+    pub async fn remove_pending_upload(&self, video_path: &Path) -> Result<()> {
+        let query = sqlx::query(
+            "DELETE FROM pending_uploads 
+            WHERE video_path = $1",
+        );
+
+        query
+            .bind(video_path.to_string_lossy())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete pending upload")?;
+
+        Ok(())
+    }
+
+    /// Pending uploads in the order they were added
+    pub async fn pending_uploads(&self) -> Result<Vec<DBPendingUpload>> {
+        let query = sqlx::query_as::<_, DBPendingUpload>(
+            "SELECT 
+              video_path, user_id, output_channel, title, 
+              mapset_id, replay_hash, settings_hash, attempts 
+            FROM pending_uploads 
+            ORDER BY created_at",
+        );
+
+        query
+            .fetch_all(&self.pool)
+            .await
+            .wrap_err("failed to fetch pending uploads")
+    }
+}
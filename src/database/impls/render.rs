@@ -0,0 +1,75 @@
+use eyre::{Result, WrapErr};
+
+use crate::database::{DBRender, Database};
+
+impl Database {
+    pub async fn upsert_render(
+        &self,
+        replay_hash: &str,
+        settings_hash: &str,
+        render: &DBRender,
+    ) -> Result<()> {
+        let query = sqlx::query(
+            "
+INSERT INTO renders (
+  replay_hash, settings_hash, link, channel_id, message_id, guild_id
+) 
+VALUES 
+  ($1, $2, $3, $4, $5, $6) ON CONFLICT (replay_hash, settings_hash) DO 
+UPDATE 
+SET 
+  link = EXCLUDED.link, 
+  channel_id = EXCLUDED.channel_id, 
+  message_id = EXCLUDED.message_id, 
+  guild_id = EXCLUDED.guild_id, 
+  rendered_at = NOW()",
+        );
+
+        query
+            .bind(replay_hash)
+            .bind(settings_hash)
+            .bind(&render.link)
+            .bind(render.channel_id)
+            .bind(render.message_id)
+            .bind(render.guild_id)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to upsert render")?;
+
+        Ok(())
+    }
+
+    pub async fn render(&self, replay_hash: &str, settings_hash: &str) -> Result<Option<DBRender>> {
+        let query = sqlx::query_as::<_, DBRender>(
+            "SELECT 
+              link, channel_id, message_id, guild_id 
+            FROM renders 
+            WHERE replay_hash = $1 
+              AND settings_hash = $2",
+        );
+
+        query
+            .bind(replay_hash)
+            .bind(settings_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .wrap_err("failed to fetch render")
+    }
+
+    pub async fn remove_render(&self, replay_hash: &str, settings_hash: &str) -> Result<()> {
+        let query = sqlx::query(
+            "DELETE FROM renders 
+            WHERE replay_hash = $1 
+              AND settings_hash = $2",
+        );
+
+        query
+            .bind(replay_hash)
+            .bind(settings_hash)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete render")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use eyre::{Result, WrapErr};
+
+use crate::{
+    core::{replay_queue::JobId, ReplayData},
+    database::{DBQueuedReplay, Database},
+};
+
+impl Database {
+    pub async fn insert_queued_replay(&self, data: &ReplayData) -> Result<()> {
+        let query = sqlx::query(
+            "
+INSERT INTO replay_queue (
+  path, job_id, name, user_id, guild_id, input_channel, 
+  output_channel, time_start, time_end, pitch
+) 
+VALUES 
+  ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT (job_id) DO 
+UPDATE 
+SET 
+  path = EXCLUDED.path, 
+  name = EXCLUDED.name, 
+  user_id = EXCLUDED.user_id, 
+  guild_id = EXCLUDED.guild_id, 
+  input_channel = EXCLUDED.input_channel, 
+  output_channel = EXCLUDED.output_channel, 
+  time_start = EXCLUDED.time_start, 
+  time_end = EXCLUDED.time_end, 
+  pitch = EXCLUDED.pitch, 
+  in_progress = FALSE",
+        );
+
+        query
+            .bind(data.path.to_string_lossy())
+            .bind(data.id.to_string())
+            .bind(&data.name)
+            .bind(data.user.get() as i64)
+            .bind(data.guild.map(|guild| guild.get() as i64))
+            .bind(data.input_channel.get() as i64)
+            .bind(data.output_channel.get() as i64)
+            .bind(data.time_points.start as i32)
+            .bind(data.time_points.end as i32)
+            .bind(data.pitch)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to insert queued replay")?;
+
+        Ok(())
+    }
+
+    pub async fn set_queued_replay_in_progress(&self, job_id: JobId) -> Result<()> {
+        let query = sqlx::query(
+            "UPDATE replay_queue 
+            SET in_progress = TRUE 
+            WHERE job_id = $1",
+        );
+
+        query
+            .bind(job_id.to_string())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to mark queued replay as in progress")?;
+
+        Ok(())
+    }
+
+    /// Takes anything that displays as job id so that rows with invalid ids can be removed too
+    pub async fn remove_queued_replay(&self, job_id: impl Display) -> Result<()> {
+        let query = sqlx::query(
+            "DELETE FROM replay_queue 
+            WHERE job_id = $1",
+        );
+
+        query
+            .bind(job_id.to_string())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete queued replay")?;
+
+        Ok(())
+    }
+
+    pub async fn remove_queued_replays(&self, replays: &[ReplayData]) -> Result<()> {
+        let query = sqlx::query(
+            "DELETE FROM replay_queue 
+            WHERE job_id = ANY($1)",
+        );
+
+        let job_ids: Vec<_> = replays.iter().map(|data| data.id.to_string()).collect();
+
+        query
+            .bind(job_ids)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete queued replays")?;
+
+        Ok(())
+    }
+
+    /// Replays that were in progress come first, then in the order they were queued
+    pub async fn queued_replays(&self) -> Result<Vec<DBQueuedReplay>> {
+        let query = sqlx::query_as::<_, DBQueuedReplay>(
+            "SELECT 
+              path, job_id, name, user_id, guild_id, input_channel, 
+              output_channel, time_start, time_end, pitch, in_progress 
+            FROM replay_queue 
+            ORDER BY in_progress DESC, queued_at",
+        );
+
+        query
+            .fetch_all(&self.pool)
+            .await
+            .wrap_err("failed to fetch queued replays")
+    }
+}
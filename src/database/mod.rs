@@ -1,6 +1,8 @@
 use eyre::Result;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 
+pub use self::models::{DBPendingUpload, DBQueuedReplay, DBRender};
+
 mod impls;
 mod models;
 
@@ -1 +1,6 @@
+pub use self::{pending_upload::DBPendingUpload, render::DBRender, replay_queue::DBQueuedReplay};
+
+mod pending_upload;
+mod render;
+mod replay_queue;
 mod server_blacklist;
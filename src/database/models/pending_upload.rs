@@ -0,0 +1,14 @@
+use sqlx::FromRow;
+
+#[derive(Debug, FromRow)]
+pub struct DBPendingUpload {
+    pub video_path: String,
+    pub user_id: i64,
+    pub output_channel: i64,
+    pub title: String,
+    pub mapset_id: i32,
+    pub replay_hash: String,
+    /// Missing for uploads that were added before renders were deduplicated
+    pub settings_hash: Option<String>,
+    pub attempts: i32,
+}
@@ -0,0 +1,11 @@
+use sqlx::FromRow;
+
+#[derive(Debug, FromRow)]
+pub struct DBRender {
+    pub link: String,
+    /// Only set if the video was posted as attachment
+    pub channel_id: Option<i64>,
+    pub message_id: Option<i64>,
+    /// Guild of the attachment's channel, attachments in DMs have none
+    pub guild_id: Option<i64>,
+}
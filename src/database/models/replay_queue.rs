@@ -0,0 +1,16 @@
+use sqlx::FromRow;
+
+#[derive(Debug, FromRow)]
+pub struct DBQueuedReplay {
+    pub path: String,
+    pub job_id: String,
+    pub name: Option<String>,
+    pub user_id: i64,
+    pub guild_id: Option<i64>,
+    pub input_channel: i64,
+    pub output_channel: i64,
+    pub time_start: i32,
+    pub time_end: i32,
+    pub pitch: Option<f64>,
+    pub in_progress: bool,
+}
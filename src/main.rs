@@ -20,7 +20,7 @@ use tokio::{runtime::Builder as RuntimeBuilder, signal};
 
 use crate::core::{
     commands::slash::{Command, Commands},
-    event_loop, logging, BotConfig, Context, ReplayQueue,
+    event_loop, logging, BotConfig, Context, Janitor, ReplayQueue,
 };
 
 fn main() {
@@ -79,9 +79,22 @@ async fn async_main() -> Result<()> {
     let event_ctx = Arc::clone(&ctx);
     ctx.cluster.up().await;
 
+    // Re-queue replays that were pending before the last shutdown
+    if let Err(err) = ctx.replay_queue.restore(&ctx).await {
+        error!("{:?}", err.wrap_err("failed to restore replay queue"));
+    }
+
+    // Retry uploads that were still pending before the last shutdown
+    if let Err(err) = ctx.replay_queue.pending_uploads.restore(ctx.psql()).await {
+        error!("{:?}", err.wrap_err("failed to restore pending uploads"));
+    }
+
     // Process the replay queue in the background
     ReplayQueue::process(Arc::clone(&ctx));
 
+    // Remove old files and watch the free disk space in the background
+    Janitor::process(Arc::clone(&ctx));
+
     tokio::select! {
         _ = event_loop(event_ctx, events) => error!("Event loop ended"),
         res = signal::ctrl_c() => if let Err(err) = res.context("error while awaiting ctrl+c") {
@@ -5,6 +5,7 @@ pub const RED: u32 = 0xE74C3C;
 // Error messages
 pub const GENERAL_ISSUE: &str = "Something went wrong, blame mezo";
 pub const NOT_OWNER: &str = "That command can only be used by the bot owner";
+pub const DISK_FULL: &str = "The bot is running out of disk space so no new replays are accepted right now, please try again later";
 // Misc
 pub const INVITE_LINK: &str = "https://discord.com/api/oauth2/authorize?client_id=867681950818893835&permissions=117824&scope=bot";
 pub const SHISHABOT_GITHUB: &str = "https://github.com/mezodev0/shishabot";
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, time::Duration};
 
 use time::OffsetDateTime;
 
@@ -21,6 +21,13 @@ pub fn how_long_ago_dynamic(date: &OffsetDateTime) -> HowLongAgoFormatterDynamic
     HowLongAgoFormatterDynamic(date.unix_timestamp())
 }
 
+/// Dynamic timestamp for the point in time after the given duration from now.
+///
+/// Note: Doesn't work in embed footers
+pub fn from_now_dynamic(duration: Duration) -> HowLongAgoFormatterDynamic {
+    how_long_ago_dynamic(&(OffsetDateTime::now_utc() + duration))
+}
+
 #[derive(Copy, Clone)]
 pub struct HowLongAgoFormatterDynamic(i64);
 